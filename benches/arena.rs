@@ -0,0 +1,54 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use cbor_m::arena::ValueArena;
+use cbor_m::encode::Encoder;
+use cbor_m::event::Event;
+use cbor_m::value::Value;
+
+const ROUNDS: u32 = 200;
+
+/// 1000個のマップ(各マップは入れ子の配列を含めて11個の節)からなる、約1万節のデータを作成する。
+fn make_document() -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut enc = Encoder::new(&mut buf);
+
+    enc.encode_event(&Event::Array(1000)).unwrap();
+    for i in 0..1000_u64 {
+	enc.encode_event(&Event::Map(4)).unwrap();
+	enc.encode_event(&Event::TextString(b"id")).unwrap();
+	enc.encode_event(&Event::UnsignedInteger(i)).unwrap();
+	enc.encode_event(&Event::TextString(b"name")).unwrap();
+	enc.encode_event(&Event::TextString(b"item")).unwrap();
+	enc.encode_event(&Event::TextString(b"tags")).unwrap();
+	enc.encode_array(&[Event::UnsignedInteger(1), Event::UnsignedInteger(2)]).unwrap();
+	enc.encode_event(&Event::TextString(b"score")).unwrap();
+	enc.encode_f64(i as f64 / 4.0).unwrap();
+    }
+
+    buf
+}
+
+fn measure<F: FnMut()>(name: &str, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+	f();
+    }
+    let elapsed: Duration = start.elapsed();
+
+    println!("{:<8} {:>10.1?} / iter", name, elapsed / ROUNDS);
+}
+
+fn main() {
+    let data = make_document();
+
+    measure("boxed", || {
+	black_box(Value::decode(black_box(&data)).unwrap());
+    });
+
+    let mut arena = ValueArena::new();
+    measure("arena", || {
+	arena.clear();
+	black_box(arena.decode(black_box(&data)).unwrap());
+    });
+}
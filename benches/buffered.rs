@@ -0,0 +1,41 @@
+use std::fs::{self, File};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use cbor_m::encode::{BufferedEncoder, Encoder, IoSink};
+use cbor_m::event::Event;
+
+const ROUNDS: u32 = 10;
+const COUNT: u64 = 100_000;
+
+fn measure<F: FnMut()>(name: &str, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+	f();
+    }
+    let elapsed: Duration = start.elapsed();
+
+    println!("{:<10} {:>10.1?} / iter", name, elapsed / ROUNDS);
+}
+
+/// 10万個の整数をファイルへエンコードする時間を、バッファーの有無で比較する。
+fn main() {
+    let path = std::env::temp_dir().join(format!("cbor-m-bench-{}.cbor", std::process::id()));
+
+    measure("unbuffered", || {
+	let mut enc = Encoder::new(IoSink::new(File::create(&path).unwrap()));
+	for i in 0..COUNT {
+	    enc.encode_event(&Event::UnsignedInteger(black_box(i))).unwrap();
+	}
+    });
+
+    measure("buffered", || {
+	let mut enc = BufferedEncoder::new(IoSink::new(File::create(&path).unwrap()));
+	for i in 0..COUNT {
+	    enc.encode_event(&Event::UnsignedInteger(black_box(i))).unwrap();
+	}
+	enc.into_inner().unwrap();
+    });
+
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,340 @@
+use core::ops::Range;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::event::*;
+use crate::decode::*;
+use crate::misc::*;
+use crate::value::Value;
+
+/// `ValueArena`に格納された値を指すハンドル。作成したアリーナに対してだけ有効。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Debug)]
+enum Node {
+    Integer(i128),
+    Bytes(Range<usize>),
+    Text(Range<usize>),
+    Array(Range<usize>),
+    Map(Range<usize>),
+    Tag(u64, NodeId),
+    Simple(u8),
+    Float(f64),
+    Null,
+    Undefined,
+    Bool(bool)
+}
+
+/// 値の木構造を、節ごとに確保せずにまとめて格納するアリーナ型。
+///
+/// 節・配列の要素・マップの要素・バイト列・文字列をそれぞれ1つの連続した領域に格納し、子は`NodeId`で参照する。
+/// 節ごとに`Box`や`Vec`を確保する`Value`に比べて、大きなデータ項目のデコードでメモリ確保の回数が少ない。
+#[derive(Clone, Default, Debug)]
+pub struct ValueArena {
+    nodes: Vec<Node>,
+    items: Vec<NodeId>,
+    entries: Vec<(NodeId, NodeId)>,
+    bytes: Vec<u8>,
+    text: String,
+    item_stack: Vec<NodeId>,
+    entry_stack: Vec<(NodeId, NodeId)>
+}
+
+impl ValueArena {
+
+    /// 空のアリーナを作成する。
+    pub fn new() -> ValueArena {
+	ValueArena::default()
+    }
+
+    /// 格納している値をすべて削除する。確保済みの領域は再利用する。
+    pub fn clear(&mut self) {
+	self.nodes.clear();
+	self.items.clear();
+	self.entries.clear();
+	self.bytes.clear();
+	self.text.clear();
+    }
+
+    /// 格納している節の数を返す。
+    pub fn len(&self) -> usize {
+	self.nodes.len()
+    }
+
+    /// 節を1つも格納していなければ`true`を返す。
+    pub fn is_empty(&self) -> bool {
+	self.nodes.is_empty()
+    }
+
+    /// バイト列の先頭のデータ項目をデコードしてアリーナに格納し、そのハンドルを返す。
+    pub fn decode(&mut self, data: &[u8]) -> Result<NodeId> {
+	self.from_events(&mut Decoder::new(data))
+    }
+
+    /// デコーダーから次のデータ項目を構成するイベントを読み込んでアリーナに格納し、そのハンドルを返す。
+    ///
+    /// エラーの場合、途中まで格納した節はアリーナに残る。
+    pub fn from_events(&mut self, decoder: &mut Decoder) -> Result<NodeId> {
+	let item_len = self.item_stack.len();
+	let entry_len = self.entry_stack.len();
+	let result = self.build(decoder, 0);
+
+	self.item_stack.truncate(item_len);
+	self.entry_stack.truncate(entry_len);
+	result
+    }
+
+    /// 整数であれば、その値を返す。
+    pub fn as_integer(&self, id: NodeId) -> Option<i128> {
+	match self.nodes[id.0] {
+	    Node::Integer(val) => Some(val),
+	    _ => None
+	}
+    }
+
+    /// 文字列であれば、その内容を返す。
+    pub fn as_text(&self, id: NodeId) -> Option<&str> {
+	match &self.nodes[id.0] {
+	    Node::Text(range) => Some(&self.text[range.clone()]),
+	    _ => None
+	}
+    }
+
+    /// バイト列であれば、その内容を返す。
+    pub fn as_bytes(&self, id: NodeId) -> Option<&[u8]> {
+	match &self.nodes[id.0] {
+	    Node::Bytes(range) => Some(&self.bytes[range.clone()]),
+	    _ => None
+	}
+    }
+
+    /// 配列であれば、要素のハンドルを返す。
+    pub fn as_array(&self, id: NodeId) -> Option<&[NodeId]> {
+	match &self.nodes[id.0] {
+	    Node::Array(range) => Some(&self.items[range.clone()]),
+	    _ => None
+	}
+    }
+
+    /// 連想配列であれば、キーと値のハンドルの組を返す。
+    pub fn as_map(&self, id: NodeId) -> Option<&[(NodeId, NodeId)]> {
+	match &self.nodes[id.0] {
+	    Node::Map(range) => Some(&self.entries[range.clone()]),
+	    _ => None
+	}
+    }
+
+    /// タグ付きの値であれば、タグ番号と内容のハンドルを返す。
+    pub fn as_tag(&self, id: NodeId) -> Option<(u64, NodeId)> {
+	match self.nodes[id.0] {
+	    Node::Tag(tag, content) => Some((tag, content)),
+	    _ => None
+	}
+    }
+
+    /// 浮動小数点数であれば、その値を返す。
+    pub fn as_float(&self, id: NodeId) -> Option<f64> {
+	match self.nodes[id.0] {
+	    Node::Float(val) => Some(val),
+	    _ => None
+	}
+    }
+
+    /// 真偽値であれば、その値を返す。
+    pub fn as_bool(&self, id: NodeId) -> Option<bool> {
+	match self.nodes[id.0] {
+	    Node::Bool(val) => Some(val),
+	    _ => None
+	}
+    }
+
+    /// nullであれば`true`を返す。
+    pub fn is_null(&self, id: NodeId) -> bool {
+	matches!(self.nodes[id.0], Node::Null)
+    }
+
+    /// ハンドルが指す値を`Value`に変換する。
+    pub fn to_value(&self, id: NodeId) -> Value {
+	match &self.nodes[id.0] {
+	    Node::Integer(val) => Value::Integer(*val),
+	    Node::Bytes(range) => Value::Bytes(self.bytes[range.clone()].to_vec()),
+	    Node::Text(range) => Value::Text(String::from(&self.text[range.clone()])),
+	    Node::Array(range) => Value::Array(self.items[range.clone()].iter().map(|item| self.to_value(*item)).collect()),
+	    Node::Map(range) => Value::Map(self.entries[range.clone()].iter().map(|(key, val)| (self.to_value(*key), self.to_value(*val))).collect()),
+	    Node::Tag(tag, content) => Value::Tag(*tag, Box::new(self.to_value(*content))),
+	    Node::Simple(val) => Value::Simple(*val),
+	    Node::Float(val) => Value::Float(*val),
+	    Node::Null => Value::Null,
+	    Node::Undefined => Value::Undefined,
+	    Node::Bool(val) => Value::Bool(*val)
+	}
+    }
+
+    fn push(&mut self, node: Node) -> NodeId {
+	self.nodes.push(node);
+	NodeId(self.nodes.len() - 1)
+    }
+
+    fn push_bytes(&mut self, content: &[u8]) -> Node {
+	let start = self.bytes.len();
+	self.bytes.extend_from_slice(content);
+	Node::Bytes(start..self.bytes.len())
+    }
+
+    fn push_text(&mut self, content: &str) -> Node {
+	let start = self.text.len();
+	self.text.push_str(content);
+	Node::Text(start..self.text.len())
+    }
+
+    fn finish_array(&mut self, start: usize) -> Node {
+	let begin = self.items.len();
+	self.items.extend(self.item_stack.drain(start..));
+	Node::Array(begin..self.items.len())
+    }
+
+    fn finish_map(&mut self, start: usize) -> Node {
+	let begin = self.entries.len();
+	self.entries.extend(self.entry_stack.drain(start..));
+	Node::Map(begin..self.entries.len())
+    }
+
+    fn build_entry(&mut self, decoder: &mut Decoder, depth: usize) -> Result<()> {
+	let key = self.build(decoder, depth)?;
+	let val = self.build(decoder, depth)?;
+	self.entry_stack.push((key, val));
+	Ok(())
+    }
+
+    fn build(&mut self, decoder: &mut Decoder, depth: usize) -> Result<NodeId> {
+	if depth >= MAX_NESTING_DEPTH {
+	    return Err(Error::NestingTooDeep);
+	}
+
+	let node = match decoder.decode_event()? {
+	    Event::UnsignedInteger(val) => Node::Integer(val as i128),
+	    Event::NegativeInteger(val) => Node::Integer(-1 - val as i128),
+	    Event::ByteString(content) => self.push_bytes(content),
+	    Event::TextString(content) => self.push_text(core::str::from_utf8(content).map_err(|_| Error::InvalidUtf8)?),
+	    Event::IndefiniteByteString => self.push_bytes(&decoder.read_indefinite_bytes()?),
+	    Event::IndefiniteTextString => self.push_text(&decoder.read_indefinite_text()?),
+	    Event::Array(len) => {
+		let start = self.item_stack.len();
+		for _ in 0..len {
+		    let item = self.build(decoder, depth + 1)?;
+		    self.item_stack.push(item);
+		}
+		self.finish_array(start)
+	    },
+	    Event::Map(len) => {
+		let start = self.entry_stack.len();
+		for _ in 0..len {
+		    self.build_entry(decoder, depth + 1)?;
+		}
+		self.finish_map(start)
+	    },
+	    Event::IndefiniteArray => {
+		let start = self.item_stack.len();
+		while decoder.peek_event()? != Event::Break {
+		    let item = self.build(decoder, depth + 1)?;
+		    self.item_stack.push(item);
+		}
+		decoder.decode_event()?;
+		self.finish_array(start)
+	    },
+	    Event::IndefiniteMap => {
+		let start = self.entry_stack.len();
+		while decoder.peek_event()? != Event::Break {
+		    self.build_entry(decoder, depth + 1)?;
+		}
+		decoder.decode_event()?;
+		self.finish_map(start)
+	    },
+	    Event::Tag(tag) => Node::Tag(tag, self.build(decoder, depth + 1)?),
+	    Event::Simple(SIMPLE_VALUE_FALSE) => Node::Bool(false),
+	    Event::Simple(SIMPLE_VALUE_TRUE) => Node::Bool(true),
+	    Event::Simple(SIMPLE_VALUE_NULL) => Node::Null,
+	    Event::Simple(SIMPLE_VALUE_UNDEFINED) => Node::Undefined,
+	    Event::Simple(val) => Node::Simple(val),
+	    Event::HalfFloat(bytes) => Node::Float(half_to_f64(bytes)),
+	    Event::SingleFloat(bytes) => Node::Float(f32::from_be_bytes(*bytes) as f64),
+	    Event::DoubleFloat(bytes) => Node::Float(f64::from_be_bytes(*bytes)),
+	    Event::Float(val) => Node::Float(val),
+	    Event::Break => return Err(Error::UnexpectedBreak),
+	    Event::End => return Err(Error::UnexpectedEnd)
+	};
+
+	Ok(self.push(node))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_value_arena() {
+	let data = [
+	    0xA3,
+	    0x61, 0x61, 0x83, 0x01, 0x9F, 0x20, 0x42, 0x01, 0x02, 0xFF, 0xF9, 0x3C, 0x00,
+	    0x01, 0xC1, 0x7F, 0x61, 0x78, 0x61, 0x79, 0xFF,
+	    0xBF, 0xF5, 0xF6, 0xFF, 0x80
+	];
+
+	let mut arena = ValueArena::new();
+	let root = arena.decode(&data).unwrap();
+
+	let entries = arena.as_map(root).unwrap();
+	assert_eq!(entries.len(), 3);
+	assert_eq!(arena.as_text(entries[0].0), Some("a"));
+
+	let items = arena.as_array(entries[0].1).unwrap();
+	assert_eq!(arena.as_integer(items[0]), Some(1));
+	assert_eq!(arena.as_array(items[1]).map(|inner| inner.len()), Some(2));
+	assert_eq!(arena.as_bytes(arena.as_array(items[1]).unwrap()[1]), Some(&[0x01, 0x02][..]));
+	assert_eq!(arena.as_float(items[2]), Some(1.0));
+
+	let (tag, content) = arena.as_tag(entries[1].1).unwrap();
+	assert_eq!(tag, 1);
+	assert_eq!(arena.as_text(content), Some("xy"));
+
+	let inner = arena.as_map(entries[2].0).unwrap();
+	assert_eq!(arena.as_bool(inner[0].0), Some(true));
+	assert!(arena.is_null(inner[0].1));
+	assert_eq!(arena.as_array(entries[2].1), Some(&[][..]));
+	assert_eq!(arena.as_integer(root), None);
+
+	assert_eq!(arena.to_value(root), Value::decode(&data).unwrap());
+    }
+
+    #[test]
+    fn test_value_arena_reuse() {
+	let mut arena = ValueArena::new();
+	let first = arena.decode(&[0x82, 0x61, 0x61, 0x02]).unwrap();
+	let second = arena.decode(&[0x81, 0x63, 0x62, 0x63, 0x64]).unwrap();
+
+	assert_eq!(arena.to_value(first), Value::Array(vec![Value::Text(String::from("a")), Value::Integer(2)]));
+	assert_eq!(arena.to_value(second), Value::Array(vec![Value::Text(String::from("bcd"))]));
+	assert_eq!(arena.len(), 5);
+
+	arena.clear();
+	assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn test_value_arena_err() {
+	let mut arena = ValueArena::new();
+	assert_eq!(arena.decode(&[0x82, 0x01]), Err(Error::UnexpectedEnd));
+	assert_eq!(arena.decode(&[0x81, 0xFF]), Err(Error::UnexpectedBreak));
+	assert_eq!(arena.decode(&[0x61, 0xFF]), Err(Error::InvalidUtf8));
+	assert_eq!(arena.decode(&[0x81; MAX_NESTING_DEPTH + 1]), Err(Error::NestingTooDeep));
+
+	let root = arena.decode(&[0x82, 0x01, 0x02]).unwrap();
+	assert_eq!(arena.to_value(root), Value::Array(vec![Value::Integer(1), Value::Integer(2)]));
+    }
+
+}
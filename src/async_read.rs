@@ -0,0 +1,144 @@
+
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::event::*;
+use crate::decode;
+use crate::decode::{check_chunk, decode_event, decode_head, following_bytes_len};
+use crate::read::{Error, Result};
+
+/// `AsyncRead`から必要な分だけ非同期に読み込みながらデコードするデコーダー型。
+pub struct AsyncReadDecoder<R: AsyncRead + Unpin> {
+    reader: R,
+    chunk_type: Option<u8>
+}
+
+impl<R: AsyncRead + Unpin> AsyncReadDecoder<R> {
+
+    /// デコーダーを作成する。パラメーターは読み込み元。
+    pub fn new(reader: R) -> AsyncReadDecoder<R> {
+	AsyncReadDecoder {
+	    reader,
+	    chunk_type: None
+	}
+    }
+
+    /// 読み込み元を取り出す。
+    pub fn into_inner(self) -> R {
+	self.reader
+    }
+
+    async fn read_initial_byte(&mut self) -> Result<Option<u8>> {
+	let mut buf = [0_u8; 1];
+
+	loop {
+	    match self.reader.read(&mut buf).await {
+		Ok(0) => return Ok(None),
+		Ok(_) => return Ok(Some(buf[0])),
+		Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+		Err(err) => return Err(Error::IoError(err))
+	    }
+	}
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+	match self.reader.read_exact(buf).await {
+	    Ok(_) => Ok(()),
+	    Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Err(Error::Decode(decode::Error::UnexpectedEnd)),
+	    Err(err) => Err(Error::IoError(err))
+	}
+    }
+
+    async fn read_content(&mut self, len: u64) -> Result<Vec<u8>> {
+	let mut content = Vec::new();
+
+	if let Err(err) = (&mut self.reader).take(len).read_to_end(&mut content).await {
+	    return Err(Error::IoError(err));
+	}
+
+	if content.len() as u64 == len {
+	    Ok(content)
+	} else {
+	    Err(Error::Decode(decode::Error::UnexpectedEnd))
+	}
+    }
+
+    /// 次のイベントを取得する。
+    pub async fn next_event(&mut self) -> Result<OwnedEvent> {
+	let mut buf = [0_u8; 9];
+
+	buf[0] = match self.read_initial_byte().await? {
+	    Some(ib) => ib,
+	    None => return Ok(OwnedEvent::End)
+	};
+
+	let len = 1 + following_bytes_len(buf[0])?;
+	self.read_exact(&mut buf[1..len]).await?;
+
+	let (head, _) = decode_head(&buf[..len])?;
+
+	let content;
+	let event = match (head.major_type(), head.argument()) {
+	    (0x40, Some(arg)) => {
+		content = self.read_content(arg).await?;
+		Event::ByteString(&content)
+	    },
+	    (0x60, Some(arg)) => {
+		content = self.read_content(arg).await?;
+		Event::TextString(&content)
+	    },
+	    _ => decode_event(&buf[..len])?.0
+	};
+
+	self.chunk_type = check_chunk(self.chunk_type, &event)?;
+
+	Ok(OwnedEvent::from(event))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::io::Builder;
+
+    #[test]
+    fn test_async_read_decoder() {
+	let reader = Builder::new()
+	    .read(&[0xA2, 0x65])
+	    .read(&[0x68, 0x65, 0x6C])
+	    .read(&[0x6C, 0x6F, 0x19])
+	    .read(&[0x01])
+	    .read(&[0x2C, 0x5F, 0x42, 0x01])
+	    .read(&[0x02, 0xFF, 0x39, 0x01, 0x00])
+	    .build();
+	let mut dec = AsyncReadDecoder::new(reader);
+
+	tokio_test::block_on(async {
+	    assert_eq!(dec.next_event().await.unwrap(), OwnedEvent::Map(2));
+	    assert_eq!(dec.next_event().await.unwrap(), OwnedEvent::TextString(b"hello".to_vec()));
+	    assert_eq!(dec.next_event().await.unwrap(), OwnedEvent::UnsignedInteger(300));
+	    assert_eq!(dec.next_event().await.unwrap(), OwnedEvent::IndefiniteByteString);
+	    assert_eq!(dec.next_event().await.unwrap(), OwnedEvent::ByteString(vec![0x01, 0x02]));
+	    assert_eq!(dec.next_event().await.unwrap(), OwnedEvent::Break);
+	    assert_eq!(dec.next_event().await.unwrap(), OwnedEvent::NegativeInteger(256));
+	    assert_eq!(dec.next_event().await.unwrap(), OwnedEvent::End);
+	});
+    }
+
+    #[test]
+    fn test_async_read_decoder_err() {
+	tokio_test::block_on(async {
+	    let mut dec = AsyncReadDecoder::new(Builder::new().read(&[0x19, 0x01]).build());
+	    assert!(matches!(dec.next_event().await, Err(Error::Decode(decode::Error::UnexpectedEnd))));
+
+	    let mut dec = AsyncReadDecoder::new(Builder::new().read(&[0x5F, 0x01]).build());
+	    assert!(matches!(dec.next_event().await, Ok(OwnedEvent::IndefiniteByteString)));
+	    assert!(matches!(dec.next_event().await, Err(Error::Decode(decode::Error::InvalidChunk))));
+
+	    let mut dec = AsyncReadDecoder::new(Builder::new().read_error(io::Error::other("failure")).build());
+	    assert!(matches!(dec.next_event().await, Err(Error::IoError(_))));
+	});
+    }
+
+}
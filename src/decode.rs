@@ -1,9 +1,67 @@
 
-use std::result;
+use core::fmt;
+use core::result;
+use core::ops::ControlFlow;
+#[cfg(feature = "alloc")]
+use core::ops::Range;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
 use crate::event::*;
+use crate::misc::{f64_to_half, half_to_f64, SimpleValue};
+use crate::raw::minimal_arg_width;
+use crate::tags::{STANDARD_DATETIME, EPOCH_DATETIME, POSITIVE_BIGNUM, NEGATIVE_BIGNUM, ENCODED_CBOR, URI};
 
+/// メジャータイプ。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MajorType {
+    /// 符号なし整数(0)。
+    Unsigned,
+
+    /// 負の整数(1)。
+    Negative,
+
+    /// バイト列(2)。
+    ByteString,
+
+    /// 文字列(3)。
+    TextString,
+
+    /// 配列(4)。
+    Array,
+
+    /// マップ(5)。
+    Map,
+
+    /// タグ(6)。
+    Tag,
+
+    /// 単純値・浮動小数点数(7)。
+    SimpleOrFloat
+}
+
+impl MajorType {
+
+    /// データ項目の初めのバイトからメジャータイプを得る。
+    pub fn from_initial_byte(initial_byte: u8) -> MajorType {
+	match initial_byte >> 5 {
+	    0 => MajorType::Unsigned,
+	    1 => MajorType::Negative,
+	    2 => MajorType::ByteString,
+	    3 => MajorType::TextString,
+	    4 => MajorType::Array,
+	    5 => MajorType::Map,
+	    6 => MajorType::Tag,
+	    _ => MajorType::SimpleOrFloat
+	}
+    }
+
+}
+
+/// データ項目の先頭部分。初めのバイトと、それに続く引数のバイト列からなる。
 #[derive(Clone, Copy, PartialEq, Debug)]
-struct Head<'a> {
+pub struct Head<'a> {
     initial_byte: u8,
     following_bytes: &'a [u8]
 }
@@ -20,15 +78,23 @@ impl<'a> Head<'a> {
 	}
     }
 
-    fn major_type(&self) -> u8 {
+    /// メジャータイプを初めのバイトの上位3ビットをマスクした値(`0x00`〜`0xE0`)で返す。
+    pub fn major_type(&self) -> u8 {
 	self.initial_byte & Self::MAJOR_TYPE_MASK
     }
 
-    fn additional_information(&self) -> u8 {
+    /// メジャータイプを列挙型で返す。
+    pub fn major_type_enum(&self) -> MajorType {
+	MajorType::from_initial_byte(self.initial_byte)
+    }
+
+    /// 初めのバイトの下位5ビットを返す。
+    pub fn additional_information(&self) -> u8 {
 	self.initial_byte & Self::ADDITIONAL_INFORMATION_MASK
     }
 
-    fn argument(&self) -> Option<u64> {
+    /// 引数を返す。不定長の場合や先頭部分が不正な場合は`None`を返す。
+    pub fn argument(&self) -> Option<u64> {
 	if !self.is_sound() {
 	    return None;
 	}
@@ -50,20 +116,12 @@ impl<'a> Head<'a> {
 	}
     }
     
-    fn is_sound(&self) -> bool {
-	match self.additional_information() {
-	    0..24 => self.following_bytes.len() == 0,
-	    24 => self.following_bytes.len() == 1,
-	    25 => self.following_bytes.len() == 2,
-	    26 => self.following_bytes.len() == 4,
-	    27 => self.following_bytes.len() == 8,
-	    28 | 29 | 30 => false,
-	    31 => match self.major_type() {
-		0x40 | 0x60 | 0x80 | 0xA0 | 0xE0 => true,
-		0x00 | 0x20 | 0xC0 => false,
-		_ => panic!("unreachable")
-	    },
-	    _ => panic!("unreachable")
+    /// 下位5ビットと引数のバイト列の長さが整合しているか検査する。
+    pub fn is_sound(&self) -> bool {
+	match following_bytes_len(self.initial_byte) {
+	    Ok(len) if self.additional_information() == 31 => len == self.following_bytes.len() && matches!(self.major_type(), 0x40 | 0x60 | 0x80 | 0xA0 | 0xE0),
+	    Ok(len) => len == self.following_bytes.len(),
+	    Err(_) => false
 	}
     }
     
@@ -74,48 +132,159 @@ impl<'a> Eq for Head<'a> {}
 /// デコード時に発生するエラー。
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Error {
-    /// データ項目の初めのバイトの下位5ビットが予約された値である場合。
-    Reserved5BitValue,
+    /// データ項目の初めのバイトの下位5ビット(追加情報)が予約された値(28〜30)である場合。
+    ReservedAdditionalInfo,
 
     /// 単純値のエンコーディングが不正である場合。
     InvalidSimpleValueEncoding,
 
     /// 予期しないデータの終端に遭遇した場合。
-    UnexpectedEnd
+    UnexpectedEnd,
+
+    /// 不定長バイト列・不定長文字列の中に型の一致しない断片が現れた場合。
+    InvalidChunk,
+
+    /// データ項目が来るべき位置にブレイクが現れた場合。
+    UnexpectedBreak,
+
+    /// データ項目の入れ子が深すぎる場合。
+    NestingTooDeep,
+
+    /// 文字列が正しいUTF-8でない場合。
+    InvalidUtf8,
+
+    /// 不定長を表せない型のデータ項目の初めのバイトの下位5ビットが31である場合。
+    InvalidIndefiniteLength,
+
+    /// フレームの長さを表すデータ項目が符号なし整数でない場合。
+    InvalidFrameLength,
+
+    /// バイト列や文字列の長さが`usize`で表せない場合。32ビット環境で長さが`u32::MAX`を超えるときに起こる。`len`は宣言された長さ。
+    LengthOverflow {
+	len: u64
+    },
+
+    /// 正規形を要求するモードで、浮動小数点数が最短の形式でない場合やNaNが`F9 7E 00`でない場合。
+    NonCanonicalFloat,
+
+    /// 正規形を要求するモードで、整数・長さ・タグ番号などの引数が最短の形式でない場合。
+    NonCanonicalArgument,
+
+    /// 正規形を要求するモードで、不定長のデータ項目が現れた場合。
+    IndefiniteInDeterministic,
+
+    /// 1つのデータ項目の後にデータが続いている場合。`offset`は続くデータの開始位置。
+    TrailingData {
+	offset: usize
+    },
+
+    /// タグの内容を検査するモードで、よく知られたタグの内容の型が正しくない場合。
+    TagContentMismatch {
+	tag: u64
+    },
+
+    /// `ArrayScope`で、宣言された要素数と読んだ要素数が一致しない場合。
+    ElementCountMismatch,
+
+    /// `seek`でバイト列の範囲外の位置に移動しようとした場合。
+    SeekOutOfBounds,
+
+    /// `expect_u64`などで、次のデータ項目が期待した型でない場合。`expected`と`found`は型の名前。
+    TypeMismatch {
+	expected: &'static str,
+	found: &'static str
+    }
 }
 
 impl Eq for Error {}
 
 pub type Result<T> = result::Result<T, Error>;
 
-/// デコーダー型。
-pub struct Decoder<'a> {
-    data: &'a [u8]
+/// `skip_value`などで許容する入れ子の深さの上限。
+pub const MAX_NESTING_DEPTH: usize = 256;
+
+/// デコーダーの位置と状態を記録する型。`Decoder::bookmark`で作成する。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Bookmark {
+    offset: usize,
+    chunk_type: Option<u8>,
+    pending_tag: Option<u64>
 }
 
-fn decode_head<'a>(data: &'a [u8]) -> Result<(Head<'a>, &'a [u8])> {
-    if data.is_empty() {
-	panic!("INTERNAL ERROR: decode_head on an empty byte string.");
+impl Bookmark {
+
+    /// デコーダーを作成したときのバイト列の先頭からのバイト数を返す。
+    pub fn offset(&self) -> usize {
+	self.offset
     }
 
-    let ib = data[0];
-    let rest = &data[1..];
+}
+
+/// `Decoder::drive`でデコードしたイベントを受け取るトレイト。
+pub trait EventVisitor {
 
-    let ai = ib & Head::ADDITIONAL_INFORMATION_MASK;
+    /// イベントを1つ受け取る。`ControlFlow::Break`を返すとデコードを停止する。
+    fn visit(&mut self, event: &Event) -> ControlFlow<()>;
+
+}
+
+/// デコーダー型。
+#[derive(Clone)]
+pub struct Decoder<'a> {
+    original: &'a [u8],
+    data: &'a [u8],
+    chunk_type: Option<u8>,
+    coalesce: bool,
+    canonical: bool,
+    validate_tags: bool,
+    pending_tag: Option<u64>,
+    float_conversion: bool
+}
+
+fn type_name(event: &Event) -> &'static str {
+    use Event::*;
+    match event {
+	UnsignedInteger(_) => "unsigned integer",
+	NegativeInteger(_) => "negative integer",
+	ByteString(_) => "byte string",
+	TextString(_) => "text string",
+	Array(_) => "array",
+	Map(_) => "map",
+	IndefiniteByteString => "indefinite byte string",
+	IndefiniteTextString => "indefinite text string",
+	IndefiniteArray => "indefinite array",
+	IndefiniteMap => "indefinite map",
+	Tag(_) => "tag",
+	Simple(20 | 21) => "boolean",
+	Simple(22) => "null",
+	Simple(23) => "undefined",
+	Simple(_) => "simple value",
+	HalfFloat(_) | SingleFloat(_) | DoubleFloat(_) | Float(_) => "float",
+	Break => "break",
+	End => "end"
+    }
+}
 
-    if ai == 28 || ai == 29 || ai == 30 {
-	return Err(Error::Reserved5BitValue);
+/// 初めのバイトの追加情報から、引数のバイト列の長さを求める。予約された追加情報の判定はここに集約する。
+pub(crate) fn following_bytes_len(initial_byte: u8) -> Result<usize> {
+    match initial_byte & Head::ADDITIONAL_INFORMATION_MASK {
+	0..24 | 31 => Ok(0),
+	24 => Ok(1),
+	25 => Ok(2),
+	26 => Ok(4),
+	27 => Ok(8),
+	_ => Err(Error::ReservedAdditionalInfo)
     }
+}
 
-    let bytes_len = match ai {
-	0..24 | 31 => 0,
-	24 => 1,
-	25 => 2,
-	26 => 4,
-	27 => 8,
-	_ => panic!("unreachable")
+pub(crate) fn decode_head<'a>(data: &'a [u8]) -> Result<(Head<'a>, &'a [u8])> {
+    let (ib, rest) = match data.split_first() {
+	Some((&ib, rest)) => (ib, rest),
+	None => return Err(Error::UnexpectedEnd)
     };
 
+    let bytes_len = following_bytes_len(ib)?;
+
     if rest.len() >= bytes_len {
 	let bytes = &rest[0..bytes_len];
 	let rest = &rest[bytes_len..];
@@ -126,7 +295,7 @@ fn decode_head<'a>(data: &'a [u8]) -> Result<(Head<'a>, &'a [u8])> {
     }
 }
 
-fn decode_bytes<'a>(data: &'a [u8], count: usize) -> Result<(&'a [u8], &'a [u8])> {
+fn decode_bytes(data: &[u8], count: usize) -> Result<(&[u8], &[u8])> {
     if data.len() >= count {
 	Ok((&data[0..count], &data[count..]))
     } else {
@@ -134,7 +303,41 @@ fn decode_bytes<'a>(data: &'a [u8], count: usize) -> Result<(&'a [u8], &'a [u8])
     }
 }
 
-fn decode_event<'a>(data: &'a [u8]) -> Result<(Event<'a>, &'a [u8])> {
+fn content_len(len: u64) -> Result<usize> {
+    checked_len(len, usize::MAX)
+}
+
+fn checked_len(len: u64, max: usize) -> Result<usize> {
+    match usize::try_from(len) {
+	Ok(val) if val <= max => Ok(val),
+	_ => Err(Error::LengthOverflow { len })
+    }
+}
+
+/// バイト列の先頭からイベントを1つデコードし、イベントと残りのバイト列を返す。バイト列が空の場合は`End`を返す。
+///
+/// メモリを確保せず、状態も持たない。不定長文字列の断片の検査やタグの内容の検査など、`Decoder`が行う前後のイベントにまたがる検査は行わない。
+/// データ項目の先頭部分だけをデコードする場合は`raw::decode_head`を使う。
+///
+/// ```
+/// use cbor_m::decode::decode_event;
+/// use cbor_m::event::Event;
+///
+/// let mut data: &[u8] = &[0x82, 0x01, 0x61, 0x61];
+/// let mut events = Vec::new();
+///
+/// loop {
+///     let (event, rest) = decode_event(data).unwrap();
+///     if event == Event::End {
+///         break;
+///     }
+///     events.push(event);
+///     data = rest;
+/// }
+///
+/// assert_eq!(events, [Event::Array(2), Event::UnsignedInteger(1), Event::TextString(b"a")]);
+/// ```
+pub fn decode_event<'a>(data: &'a [u8]) -> Result<(Event<'a>, &'a [u8])> {
     if data.is_empty() {
 	return Ok((Event::End, data));
     }
@@ -142,238 +345,2085 @@ fn decode_event<'a>(data: &'a [u8]) -> Result<(Event<'a>, &'a [u8])> {
     let (head, rest) = decode_head(data)?;
 
     match head.major_type() >> 5 {
-	0 => Ok((Event::UnsignedInteger(head.argument().unwrap()), rest)),
-	1 => Ok((Event::NegativeInteger(head.argument().unwrap()), rest)),
-	2 => if head.additional_information() == 31 {
-	    Ok((Event::IndefiniteByteString, rest))
-	} else if let Ok(len) = usize::try_from(head.argument().unwrap()) {
-	    let (content, rest) = decode_bytes(rest, len)?;
-	    Ok((Event::ByteString(content), rest))
-	} else {
-	    Err(Error::UnexpectedEnd)
+	0 => match head.argument() {
+	    Some(val) => Ok((Event::UnsignedInteger(val), rest)),
+	    None => Err(Error::InvalidIndefiniteLength)
 	},
-	3 => if head.additional_information() == 31 {
-	    Ok((Event::IndefiniteTextString, rest))
-	} else if let Ok(len) = usize::try_from(head.argument().unwrap()) {
-	    let (content, rest) = decode_bytes(rest, len)?;
-	    Ok((Event::TextString(content), rest))
-	} else {
-	    Err(Error::UnexpectedEnd)
+	1 => match head.argument() {
+	    Some(val) => Ok((Event::NegativeInteger(val), rest)),
+	    None => Err(Error::InvalidIndefiniteLength)
 	},
-	4 => if head.additional_information() == 31 {
-	    Ok((Event::IndefiniteArray, rest))
-	} else {
-	    Ok((Event::Array(head.argument().unwrap()), rest))
+	2 => match head.argument() {
+	    None => Ok((Event::IndefiniteByteString, rest)),
+	    Some(len) => {
+		let (content, rest) = decode_bytes(rest, content_len(len)?)?;
+		Ok((Event::ByteString(content), rest))
+	    }
 	},
-	5 => if head.additional_information() == 31 {
-	    Ok((Event::IndefiniteMap, rest))
-	} else {
-	    Ok((Event::Map(head.argument().unwrap()), rest))
+	3 => match head.argument() {
+	    None => Ok((Event::IndefiniteTextString, rest)),
+	    Some(len) => {
+		let (content, rest) = decode_bytes(rest, content_len(len)?)?;
+		Ok((Event::TextString(content), rest))
+	    }
+	},
+	4 => match head.argument() {
+	    None => Ok((Event::IndefiniteArray, rest)),
+	    Some(len) => Ok((Event::Array(len), rest))
+	},
+	5 => match head.argument() {
+	    None => Ok((Event::IndefiniteMap, rest)),
+	    Some(len) => Ok((Event::Map(len), rest))
 	},
-	6 => Ok((Event::Tag(head.argument().unwrap()), rest)),
-	7 => match head.additional_information() {
+	6 => match head.argument() {
+	    Some(val) => Ok((Event::Tag(val), rest)),
+	    None => Err(Error::InvalidIndefiniteLength)
+	},
+	_ => match head.additional_information() {
 	    0..24 => Ok((Event::Simple(head.additional_information()), rest)),
-	    24 => {
-		let val = head.argument().unwrap();
-		if val < 32 {
-		    Err(Error::InvalidSimpleValueEncoding)
-		} else {
-		    Ok((Event::Simple(val as u8), rest))
-		}
+	    24 => match head.argument() {
+		Some(val) if val >= 32 => Ok((Event::Simple(val as u8), rest)),
+		_ => Err(Error::InvalidSimpleValueEncoding)
 	    },
-	    25 => Ok((Event::HalfFloat(head.following_bytes.try_into().unwrap()), rest)),
-	    26 => Ok((Event::SingleFloat(head.following_bytes.try_into().unwrap()), rest)),
-	    27 => Ok((Event::DoubleFloat(head.following_bytes.try_into().unwrap()), rest)),
-	    31 => Ok((Event::Break, rest)),
-	    _ => panic!("unreachable")
-	},
-	_ => panic!("unreachable")
+	    25 => match head.following_bytes.try_into() {
+		Ok(bytes) => Ok((Event::HalfFloat(bytes), rest)),
+		Err(_) => Err(Error::UnexpectedEnd)
+	    },
+	    26 => match head.following_bytes.try_into() {
+		Ok(bytes) => Ok((Event::SingleFloat(bytes), rest)),
+		Err(_) => Err(Error::UnexpectedEnd)
+	    },
+	    27 => match head.following_bytes.try_into() {
+		Ok(bytes) => Ok((Event::DoubleFloat(bytes), rest)),
+		Err(_) => Err(Error::UnexpectedEnd)
+	    },
+	    // 予約された追加情報は`decode_head`で拒否されているので、残りは31だけである。
+	    _ => Ok((Event::Break, rest))
+	}
     }
 }
 
-impl<'a> Decoder<'a> {
-
-    /// デコーダーを作成する。パラメーターはデコード対象のバイト列。
-    pub fn new(data: &'a [u8]) -> Decoder<'a> {
-	Decoder { data }
+fn check_canonical(data: &[u8], event: &Event) -> Result<()> {
+    use Event::*;
+    match *event {
+	IndefiniteByteString | IndefiniteTextString | IndefiniteArray | IndefiniteMap => return Err(Error::IndefiniteInDeterministic),
+	UnsignedInteger(arg) | NegativeInteger(arg) | Array(arg) | Map(arg) | Tag(arg) => check_argument(data, arg)?,
+	ByteString(content) | TextString(content) => check_argument(data, content.len() as u64)?,
+	_ => {}
     }
 
-    /// 次のイベントを取得する。
-    pub fn decode_event(&mut self) -> Result<Event> {
-	let (event, rest) = decode_event(self.data)?;
+    let canonical = match *event {
+	Event::HalfFloat(bytes) => {
+	    let val = half_to_f64(bytes);
+	    !val.is_nan() || *bytes == [0x7E, 0x00]
+	},
+	Event::SingleFloat(bytes) => {
+	    let val = f32::from_be_bytes(*bytes) as f64;
+	    !val.is_nan() && f64_to_half(val).is_none()
+	},
+	Event::DoubleFloat(bytes) => {
+	    let val = f64::from_be_bytes(*bytes);
+	    !val.is_nan() && f64_to_half(val).is_none() && (val as f32) as f64 != val
+	},
+	_ => true
+    };
 
-	self.data = rest;
+    if canonical {
+	Ok(())
+    } else {
+	Err(Error::NonCanonicalFloat)
+    }
+}
+
+fn check_argument(data: &[u8], argument: u64) -> Result<()> {
+    let (head, _) = decode_head(data)?;
 
-	Ok(event)
+    if head.following_bytes.len() == minimal_arg_width(argument) as usize {
+	Ok(())
+    } else {
+	Err(Error::NonCanonicalArgument)
     }
-    
 }
-    
-#[cfg(test)]
-mod tests {
-    use super::*;
-    
-    #[test]
-    fn test_head() {
-	let head = Head::new(0x15, &[]);
-	assert_eq!(head.initial_byte, 0x15);
-	assert_eq!(head.major_type(), 0x00);
-	assert_eq!(head.additional_information(), 0x15);
-	assert_eq!(head.following_bytes, []);
-	assert!(head.is_sound());
 
-	let head = Head::new(0x58, &[0xFB]);
-	assert_eq!(head.initial_byte, 0x58);
-	assert_eq!(head.major_type(), 0x40);
-	assert_eq!(head.additional_information(), 0x18);
-	assert_eq!(head.following_bytes, [0xFB]);
-	assert!(head.is_sound());
+fn check_tag_content(tag: u64, event: &Event) -> Result<()> {
+    use Event::*;
+    let valid = match tag {
+	STANDARD_DATETIME | URI => matches!(event, TextString(_) | IndefiniteTextString),
+	EPOCH_DATETIME => matches!(event, UnsignedInteger(_) | NegativeInteger(_) | HalfFloat(_) | SingleFloat(_) | DoubleFloat(_) | Float(_)),
+	POSITIVE_BIGNUM | NEGATIVE_BIGNUM | ENCODED_CBOR => matches!(event, ByteString(_) | IndefiniteByteString),
+	_ => true
+    };
+
+    if valid {
+	Ok(())
+    } else {
+	Err(Error::TagContentMismatch { tag })
     }
+}
 
-    #[test]
-    fn test_head_argument() {
-	let head = Head::new(0x31, &[]);
-	assert_eq!(head.argument(), Some(0x11));
+pub(crate) fn check_chunk(chunk_type: Option<u8>, event: &Event) -> Result<Option<u8>> {
+    match (chunk_type, event) {
+	(None, Event::IndefiniteByteString) => Ok(Some(0x40)),
+	(None, Event::IndefiniteTextString) => Ok(Some(0x60)),
+	(None, _) => Ok(None),
+	(Some(_), Event::Break) => Ok(None),
+	(Some(0x40), Event::ByteString(_)) | (Some(0x60), Event::TextString(_)) => Ok(chunk_type),
+	(Some(_), Event::End) => Ok(chunk_type),
+	(Some(_), _) => Err(Error::InvalidChunk)
+    }
+}
 
-	let head = Head::new(0x58, &[0xAB]);
-	assert_eq!(head.argument(), Some(0xAB));
+struct HexPreview<'a>(&'a [u8]);
 
-	let head = Head::new(0x79, &[0xA5, 0xE6]);
-	assert_eq!(head.argument(), Some(0xA5E6));
+impl<'a> fmt::Debug for HexPreview<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	const PREVIEW_LEN: usize = 8;
 
-	let head = Head::new(0x9A, &[0x98, 0x36, 0x96, 0x0D]);
-	assert_eq!(head.argument(), Some(0x9836_960D));
+	for (i, b) in self.0.iter().take(PREVIEW_LEN).enumerate() {
+	    if i > 0 {
+		f.write_str(" ")?;
+	    }
+	    write!(f, "{:02X}", b)?;
+	}
 
-	let head = Head::new(0xBB, &[0x53, 0x84, 0xC4, 0x60, 0xFD, 0xB0, 0x04, 0xC4]);
-	assert_eq!(head.argument(), Some(0x5384_C460_FDB0_04C4));
+	if self.0.len() > PREVIEW_LEN {
+	    f.write_str(" ..")?;
+	}
 
-	let head = Head::new(0xFF, &[]);
-	assert_eq!(head.argument(), None);
+	Ok(())
     }
+}
 
-    #[test]
-    fn test_decode_head() {
-	let bytes = &[0x0C, 0x6B];
-	assert_eq!(decode_head(bytes), Ok((Head::new(0x0C, &bytes[1..1]), &bytes[1..])));
+impl<'a> fmt::Debug for Decoder<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	f.debug_struct("Decoder")
+	    .field("remaining", &self.data.len())
+	    .field("preview", &HexPreview(self.data))
+	    .finish()
+    }
+}
 
-	let bytes = &[0xF8, 0xDB, 0x02, 0x35];
-	assert_eq!(decode_head(bytes), Ok((Head::new(0xF8, &bytes[1..2]), &bytes[2..])));
+impl<'a> Decoder<'a> {
 
-	let bytes = &[0x99, 0x78, 0x14, 0xF4, 0xC6, 0xBE];
-	assert_eq!(decode_head(bytes), Ok((Head::new(0x99, &bytes[1..3]), &bytes[3..])));
+    /// デコーダーを作成する。パラメーターはデコード対象のバイト列。
+    pub fn new(data: &'a [u8]) -> Decoder<'a> {
+	Decoder {
+	    original: data,
+	    data,
+	    chunk_type: None,
+	    coalesce: false,
+	    canonical: false,
+	    validate_tags: false,
+	    pending_tag: None,
+	    float_conversion: false
+	}
+    }
 
-	let bytes = &[0x3A, 0x14, 0xE3, 0x17, 0x19, 0x49];
-	assert_eq!(decode_head(bytes), Ok((Head::new(0x3A, &bytes[1..5]), &bytes[5..])));
+    /// 正規形を要求するモードのデコーダーを作成する。`new`の後に`require_canonical(true)`を呼び出すことと同じ。
+    pub fn deterministic(data: &'a [u8]) -> Decoder<'a> {
+	let mut dec = Decoder::new(data);
+	dec.require_canonical(true);
+	dec
+    }
 
-	let bytes = &[0xBB, 0x9E, 0x1E, 0x5F, 0xD7, 0xE3, 0xA4, 0x07, 0xE1];
-	assert_eq!(decode_head(bytes), Ok((Head::new(0xBB, &bytes[1..9]), &bytes[9..])));
-		      
+    /// 浮動小数点数を値に変換するデコーダーを作成する。
+    ///
+    /// このデコーダーは`HalfFloat`・`SingleFloat`・`DoubleFloat`の代わりに、`f64`に変換した値を持つ`Float`を返す。
+    pub fn with_float_conversion(data: &'a [u8]) -> Decoder<'a> {
+	let mut dec = Decoder::new(data);
+	dec.float_conversion = true;
+	dec
     }
 
-    #[test]
-    fn test_decode_head_err() {
-	let bytes = &[0x1C];
-	assert_eq!(decode_head(bytes), Err(Error::Reserved5BitValue));
+    /// 正規形を要求するモードを設定する。
+    ///
+    /// 有効な場合、RFC 8949 4.2節の決定的エンコーディングに従わないデータ項目をエラーとする。
+    /// 整数・長さ・タグ番号などの引数は最短の形式でなければならず、不定長のデータ項目は使用できない。
+    /// 浮動小数点数は値を変えない最短の形式でなければならず、NaNは`F9 7E 00`でなければならない。
+    pub fn require_canonical(&mut self, enable: bool) {
+	self.canonical = enable;
+    }
 
-	let bytes = &[0x5A, 0x00, 0x00, 0x00];
-	assert_eq!(decode_head(bytes), Err(Error::UnexpectedEnd));
+    /// よく知られたタグの内容の型を検査するモードを設定する。
+    ///
+    /// 有効な場合、タグ0・32の内容が文字列、タグ1の内容が数値、タグ2・3・24の内容がバイト列でなければ`TagContentMismatch`とする。
+    pub fn validate_tags(&mut self, enable: bool) {
+	self.validate_tags = enable;
     }
 
-    #[test]
-    fn test_decode_bytes() {
-	let bytes = &[0x84, 0xD8, 0xFF, 0x70];
-	assert_eq!(decode_bytes(bytes, 3), Ok((&bytes[0..3], &bytes[3..])));
+    fn check_event(&self, event: &Event) -> Result<(Option<u8>, Option<u64>)> {
+	if self.canonical {
+	    check_canonical(self.data, event)?;
+	}
+	let chunk_type = check_chunk(self.chunk_type, event)?;
 
-	let bytes = &[0x34, 0x1B];
-	assert_eq!(decode_bytes(bytes, 3), Err(Error::UnexpectedEnd));
+	if !self.validate_tags {
+	    return Ok((chunk_type, None));
+	}
+	if let Some(tag) = self.pending_tag {
+	    if *event == Event::End {
+		return Ok((chunk_type, self.pending_tag));
+	    }
+	    check_tag_content(tag, event)?;
+	}
+
+	match *event {
+	    Event::Tag(tag) => Ok((chunk_type, Some(tag))),
+	    _ => Ok((chunk_type, None))
+	}
     }
 
-    #[test]
-    fn test_decode_event_integer() {
-	let mut dec = Decoder::new(&[
-	    0x0B,
-	    0x18, 0x8C,
-	    0x39, 0xB9, 0x37
-	]);
+    fn convert_float(&self, event: Event<'a>) -> Event<'a> {
+	if !self.float_conversion {
+	    return event;
+	}
 
-	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(0x0B)));
-	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(0x8C)));
-	assert_eq!(dec.decode_event(), Ok(Event::NegativeInteger(0xB937)));
-	assert_eq!(dec.decode_event(), Ok(Event::End));
+	match event {
+	    Event::HalfFloat(bytes) => Event::Float(half_to_f64(bytes)),
+	    Event::SingleFloat(bytes) => Event::Float(f32::from_be_bytes(*bytes) as f64),
+	    Event::DoubleFloat(bytes) => Event::Float(f64::from_be_bytes(*bytes)),
+	    _ => event
+	}
     }
 
-    #[test]
-    fn test_decode_event_string() {
-	let mut dec = Decoder::new(&[
-	    0x43, 0x9D, 0x1B, 0x22,
-	    0x78, 0x01, 0x4E,
-	    0x5F,
-	    0x7F
-	]);
+    /// 次のイベントを取得する。
+    pub fn decode_event(&mut self) -> Result<Event<'a>> {
+	let (event, rest) = decode_event(self.data)?;
 
-	assert_eq!(dec.decode_event(), Ok(Event::ByteString(&[0x9D, 0x1B, 0x22])));
-	assert_eq!(dec.decode_event(), Ok(Event::TextString(&[0x4E])));
-	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
-	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteTextString));
-	assert_eq!(dec.decode_event(), Ok(Event::End));
+	(self.chunk_type, self.pending_tag) = self.check_event(&event)?;
+	self.data = rest;
+
+	Ok(self.convert_float(event))
     }
 
-    #[test]
-    fn test_decode_event_array_map() {
-	let mut dec = Decoder::new(&[
-	    0x83, 0x9F, 0xAC, 0xBF
-	]);
+    /// 不定長バイト列・不定長文字列をまとめるモードを設定する。
+    ///
+    /// 有効な場合、`decode_owned_event`は不定長バイト列・不定長文字列の断片をブレイクまで読み込み、連結した内容を持つ1つの`ByteString`・`TextString`イベントとして返す。
+    /// 連結のために内容の長さ分のメモリを確保する。`decode_event`の動作は変わらない。
+    /// 不定長文字列は連結した全体でUTF-8の検査を行うため、断片の境界で分割された文字は受け入れ、文字の途中で終わる場合は`InvalidUtf8`を返す。
+    pub fn coalesce_strings(&mut self, enable: bool) {
+	self.coalesce = enable;
+    }
 
-	assert_eq!(dec.decode_event(), Ok(Event::Array(0x03)));
-	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteArray));
-	assert_eq!(dec.decode_event(), Ok(Event::Map(0x0C)));
-	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteMap));
-	assert_eq!(dec.decode_event(), Ok(Event::End));
+    /// 次のイベントを内容を所有するイベントとして取得する。
+    #[cfg(feature = "alloc")]
+    pub fn decode_owned_event(&mut self) -> Result<OwnedEvent> {
+	let event = self.decode_event()?;
+
+	if self.coalesce {
+	    match event {
+		Event::IndefiniteByteString => return Ok(OwnedEvent::ByteString(self.read_indefinite_bytes()?)),
+		Event::IndefiniteTextString => return Ok(OwnedEvent::TextString(self.read_indefinite_text()?.into_bytes())),
+		_ => {}
+	    }
+	}
+
+	Ok(OwnedEvent::from(event))
     }
 
-    #[test]
-    fn test_decode_event_tag() {
-	let mut dec = Decoder::new(&[0xD9, 0x5E, 0xD2]);
+    /// 次のイベントを取得する。次のデータ項目がバイト列の途中で途切れている場合は、何も消費せずに`None`を返す。
+    ///
+    /// 途切れた場合は`remaining`で得られる残りのバイト列に続きのデータを連結し、`resume`で作成したデコーダーでデコードを再開する。
+    pub fn try_decode_event(&mut self) -> Result<Option<Event<'a>>> {
+	match self.decode_event() {
+	    Ok(event) => Ok(Some(event)),
+	    Err(Error::UnexpectedEnd) => Ok(None),
+	    Err(err) => Err(err)
+	}
+    }
 
-	assert_eq!(dec.decode_event(), Ok(Event::Tag(0x5ED2)));
-	assert_eq!(dec.decode_event(), Ok(Event::End));
+    /// イベントを`out`の長さまでデコードして書き込み、書き込んだイベントの数を返す。`End`は書き込まず、そこで停止する。
+    ///
+    /// 1つ以上のイベントを書き込んだ後でエラーが発生した場合は、そこまでの数を返す。エラーは次の呼び出しで返される。
+    pub fn decode_into(&mut self, out: &mut [Event<'a>]) -> Result<usize> {
+	for (count, slot) in out.iter_mut().enumerate() {
+	    match self.decode_event() {
+		Ok(Event::End) => return Ok(count),
+		Ok(event) => *slot = event,
+		Err(_) if count > 0 => return Ok(count),
+		Err(err) => return Err(err)
+	    }
+	}
+
+	Ok(out.len())
     }
 
-    #[test]
-    fn test_decode_event_simple() {
-	let mut dec = Decoder::new(&[0xE7, 0xF8, 0x5E]);
+    /// `End`までイベントをデコードし、順に`visitor`に渡す。`End`は渡さない。
+    ///
+    /// `visitor`が`ControlFlow::Break`を返した場合はそこで停止して`ControlFlow::Break`を返し、`End`まで達した場合は`ControlFlow::Continue`を返す。
+    pub fn drive<V: EventVisitor + ?Sized>(&mut self, visitor: &mut V) -> Result<ControlFlow<()>> {
+	loop {
+	    match self.decode_event()? {
+		Event::End => return Ok(ControlFlow::Continue(())),
+		event => if visitor.visit(&event).is_break() {
+		    return Ok(ControlFlow::Break(()));
+		}
+	    }
+	}
+    }
 
-	assert_eq!(dec.decode_event(), Ok(Event::Simple(0x07)));
-	assert_eq!(dec.decode_event(), Ok(Event::Simple(0x5E)));
-	assert_eq!(dec.decode_event(), Ok(Event::End));
+    /// このデコーダーの状態を引き継いで、新しいバイト列をデコードするデコーダーを作成する。
+    pub fn resume<'b>(&self, data: &'b [u8]) -> Decoder<'b> {
+	Decoder {
+	    original: data,
+	    data,
+	    chunk_type: self.chunk_type,
+	    coalesce: self.coalesce,
+	    canonical: self.canonical,
+	    validate_tags: self.validate_tags,
+	    pending_tag: self.pending_tag,
+	    float_conversion: self.float_conversion
+	}
     }
 
-    #[test]
-    fn test_decode_event_simple_err() {
-	let mut dec = Decoder::new(&[0xF8, 20]);
-	assert_eq!(dec.decode_event(), Err(Error::InvalidSimpleValueEncoding));
+    /// まだデコードされていない残りのバイト列を取得する。
+    pub fn remaining(&self) -> &'a [u8] {
+	self.data
     }
 
-    #[test]
-    fn test_decode_event_float() {
-	let mut dec = Decoder::new(&[
-	    0xF9, 0x7C, 0x00,
-	    0xFA, 0x7F, 0x80, 0x00, 0x00,
-	    0xFB, 0x7F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
-	]);
+    /// デコーダーを作成したときのバイト列の先頭から、次にデコードする位置までのバイト数を返す。
+    pub fn position(&self) -> usize {
+	self.original.len() - self.data.len()
+    }
 
-	assert_eq!(dec.decode_event(), Ok(Event::HalfFloat(&[0x7C, 0x00])));
-	assert_eq!(dec.decode_event(), Ok(Event::SingleFloat(&[0x7F, 0x80, 0x00, 0x00])));
-	assert_eq!(dec.decode_event(), Ok(Event::DoubleFloat(&[0x7F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])));
-	assert_eq!(dec.decode_event(), Ok(Event::End));
+    /// 現在の位置と状態を記録したブックマークを返す。
+    pub fn bookmark(&self) -> Bookmark {
+	Bookmark {
+	    offset: self.position(),
+	    chunk_type: self.chunk_type,
+	    pending_tag: self.pending_tag
+	}
     }
 
-    #[test]
-    fn test_decode_event_break() {
-	let mut dec = Decoder::new(&[0xFF]);
+    /// `bookmark`で記録した位置と状態に戻る。位置がバイト列の範囲外であれば`SeekOutOfBounds`を返す。
+    ///
+    /// 位置がデータ項目の先頭であるかは検査しないので、同じバイト列のデコーダーで記録したブックマークを使うこと。
+    pub fn seek(&mut self, bookmark: Bookmark) -> Result<()> {
+	if bookmark.offset > self.original.len() {
+	    return Err(Error::SeekOutOfBounds);
+	}
 
+	self.data = &self.original[bookmark.offset..];
+	self.chunk_type = bookmark.chunk_type;
+	self.pending_tag = bookmark.pending_tag;
+
+	Ok(())
+    }
+
+    /// デコーダーを作成したときのバイト列の先頭に戻る。不定長バイト列・不定長文字列やタグの途中である状態も初期化する。
+    pub fn reset(&mut self) {
+	self.data = self.original;
+	self.chunk_type = None;
+	self.pending_tag = None;
+    }
+
+    /// 次のイベントを消費せずに取得する。
+    pub fn peek_event(&self) -> Result<Event<'a>> {
+	let (event, _) = decode_event(self.data)?;
+
+	self.check_event(&event)?;
+
+	Ok(self.convert_float(event))
+    }
+
+    fn expect_with<T, F>(&mut self, expected: &'static str, f: F) -> Result<T>
+    where F: FnOnce(Event<'a>) -> Option<Result<T>> {
+	let event = self.peek_event()?;
+
+	match event {
+	    Event::Break => Err(Error::UnexpectedBreak),
+	    Event::End => Err(Error::UnexpectedEnd),
+	    _ => match f(event) {
+		Some(Ok(val)) => {
+		    self.decode_event()?;
+		    Ok(val)
+		},
+		Some(Err(err)) => Err(err),
+		None => Err(Error::TypeMismatch { expected, found: type_name(&event) })
+	    }
+	}
+    }
+
+    /// 次のデータ項目を符号なし整数としてデコードする。型が異なる場合は何も消費せずに`TypeMismatch`を返す。
+    pub fn expect_u64(&mut self) -> Result<u64> {
+	self.expect_with("unsigned integer", |event| match event {
+	    Event::UnsignedInteger(val) => Some(Ok(val)),
+	    _ => None
+	})
+    }
+
+    /// 次のデータ項目を符号なし整数または負整数としてデコードする。型が異なる場合は何も消費せずに`TypeMismatch`を返す。
+    pub fn expect_i128(&mut self) -> Result<i128> {
+	self.expect_with("integer", |event| match event {
+	    Event::UnsignedInteger(val) => Some(Ok(val as i128)),
+	    Event::NegativeInteger(val) => Some(Ok(-1 - val as i128)),
+	    _ => None
+	})
+    }
+
+    /// 次のデータ項目を定長文字列としてデコードする。型が異なる場合は何も消費せずに`TypeMismatch`を返す。
+    pub fn expect_text(&mut self) -> Result<&'a str> {
+	self.expect_with("text string", |event| match event {
+	    Event::TextString(content) => Some(core::str::from_utf8(content).map_err(|_| Error::InvalidUtf8)),
+	    _ => None
+	})
+    }
+
+    /// 次のデータ項目を定長バイト列としてデコードする。型が異なる場合は何も消費せずに`TypeMismatch`を返す。
+    pub fn expect_bytes(&mut self) -> Result<&'a [u8]> {
+	self.expect_with("byte string", |event| match event {
+	    Event::ByteString(content) => Some(Ok(content)),
+	    _ => None
+	})
+    }
+
+    /// 次のデータ項目を真偽値としてデコードする。型が異なる場合は何も消費せずに`TypeMismatch`を返す。
+    pub fn expect_bool(&mut self) -> Result<bool> {
+	self.expect_with("boolean", |event| match event.simple_value() {
+	    Some(SimpleValue::False) => Some(Ok(false)),
+	    Some(SimpleValue::True) => Some(Ok(true)),
+	    _ => None
+	})
+    }
+
+    /// 次のデータ項目を配列の先頭としてデコードし、配列長を返す。不定長配列の場合は`None`を返す。型が異なる場合は何も消費せずに`TypeMismatch`を返す。
+    pub fn expect_array_len(&mut self) -> Result<Option<u64>> {
+	self.expect_with("array", |event| match event {
+	    Event::Array(len) => Some(Ok(Some(len))),
+	    Event::IndefiniteArray => Some(Ok(None)),
+	    _ => None
+	})
+    }
+
+    /// 次のデータ項目を配列の先頭としてデコードし、要素を読み込むためのスコープを返す。型が異なる場合は何も消費せずに`TypeMismatch`を返す。
+    ///
+    /// 読み終えたら`ArrayScope::end`を呼び出して、全ての要素を読んだことを検査すること。
+    pub fn expect_array<'d>(&'d mut self) -> Result<ArrayScope<'d, 'a>> {
+	let len = self.expect_array_len()?;
+
+	Ok(ArrayScope {
+	    decoder: self,
+	    len,
+	    remaining: len
+	})
+    }
+
+    /// `Array(n)`の直後に呼び出し、`n`個の要素をそれぞれ1つのデータ項目だけを含むデコーダーとして順に返すイテレーターを作成する。
+    pub fn array_items<'d>(&'d mut self, n: u64) -> ArrayItems<'d, 'a> {
+	ArrayItems {
+	    decoder: self,
+	    remaining: n
+	}
+    }
+
+    /// `Map(n)`の直後に呼び出し、`n`個のエントリーをそれぞれキーと値のデコーダーの組として順に返すイテレーターを作成する。
+    pub fn map_entries<'d>(&'d mut self, n: u64) -> MapEntries<'d, 'a> {
+	MapEntries {
+	    decoder: self,
+	    remaining: n
+	}
+    }
+
+    /// 次のデータ項目を配列としてデコードし、各要素のエンコードされたバイト列の範囲を返す。配列全体を消費する。
+    ///
+    /// 範囲はデコーダーを作成したときのバイト列の先頭からのバイト数で表し、重なりなく順に並ぶ。
+    /// 各範囲のバイト列を別々のスレッドでデコードするといった使い方ができる。エラーの場合は何も消費しない。
+    #[cfg(feature = "alloc")]
+    pub fn array_item_ranges(&mut self) -> Result<Vec<Range<usize>>> {
+	let mut probe = self.clone();
+	let len = probe.expect_array_len()?;
+	let mut ranges = Vec::new();
+
+	loop {
+	    match len {
+		Some(len) if ranges.len() as u64 == len => break,
+		None if probe.peek_event()? == Event::Break => {
+		    probe.decode_event()?;
+		    break;
+		},
+		_ => {}
+	    }
+
+	    let start = probe.position();
+	    let item = probe.take_raw_item()?;
+	    ranges.push(start..start + item.len());
+	}
+
+	*self = probe;
+	Ok(ranges)
+    }
+
+    /// `Map(n)`の直後に呼び出し、`n`個のエントリーを文字列のキーと値のエンコードされたバイト列の組として順に返すイテレーターを作成する。
+    ///
+    /// キーが定長文字列でない場合は`TypeMismatch`を返す。
+    pub fn map_str_entries<'d>(&'d mut self, n: u64) -> MapStrEntries<'d, 'a> {
+	MapStrEntries {
+	    decoder: self,
+	    remaining: n
+	}
+    }
+
+    /// 次のデータ項目全体(先頭部分・内容・入れ子の要素を含む)のバイト数を、何も消費せずに返す。
+    pub fn next_item_len(&self) -> Result<usize> {
+	let mut probe = self.clone();
+
+	probe.skip_value()?;
+
+	Ok(self.data.len() - probe.data.len())
+    }
+
+    /// 次のデータ項目全体のエンコードされたバイト列を取得し、その後ろまで進む。エラーの場合は何も消費しない。
+    pub fn take_raw_item(&mut self) -> Result<&'a [u8]> {
+	let start = self.data;
+	let mut probe = self.clone();
+
+	probe.skip_value()?;
+	*self = probe;
+
+	Ok(&start[..start.len() - self.data.len()])
+    }
+
+    /// 次のデータ項目だけをデコードする子のデコーダーを作成する。子は設定を引き継ぎ、断片やタグの状態は引き継がない。
+    fn next_item(&mut self) -> Result<Decoder<'a>> {
+	let raw = self.take_raw_item()?;
+	let mut item = self.resume(raw);
+	item.chunk_type = None;
+	item.pending_tag = None;
+	Ok(item)
+    }
+
+    /// 次のバイト列が自己記述CBORのタグ(55799)の先頭部分`D9 D9 F7`であれば、それを消費して`true`を返す。
+    ///
+    /// そうでない場合は何も消費せずに`false`を返す。
+    pub fn skip_self_describe(&mut self) -> bool {
+	const SELF_DESCRIBE_HEAD: [u8; 3] = [0xD9, 0xD9, 0xF7];
+
+	if self.chunk_type.is_none() && self.data.starts_with(&SELF_DESCRIBE_HEAD) {
+	    self.data = &self.data[SELF_DESCRIBE_HEAD.len()..];
+	    true
+	} else {
+	    false
+	}
+    }
+
+    /// 次のデータ項目を入れ子の要素も含めて読み飛ばす。
+    pub fn skip_value(&mut self) -> Result<()> {
+	self.skip_item(0)
+    }
+
+    fn skip_item(&mut self, depth: usize) -> Result<()> {
+	if depth >= MAX_NESTING_DEPTH {
+	    return Err(Error::NestingTooDeep);
+	}
+
+	match self.decode_event()? {
+	    Event::Array(len) => {
+		for _ in 0..len {
+		    self.skip_item(depth + 1)?;
+		}
+		Ok(())
+	    },
+	    Event::Map(len) => {
+		for _ in 0..len {
+		    self.skip_item(depth + 1)?;
+		    self.skip_item(depth + 1)?;
+		}
+		Ok(())
+	    },
+	    Event::IndefiniteByteString | Event::IndefiniteTextString => {
+		while !self.skip_break()? {
+		    self.decode_event()?;
+		}
+		Ok(())
+	    },
+	    Event::IndefiniteArray => {
+		while !self.skip_break()? {
+		    self.skip_item(depth + 1)?;
+		}
+		Ok(())
+	    },
+	    Event::IndefiniteMap => {
+		while !self.skip_break()? {
+		    self.skip_item(depth + 1)?;
+		    self.skip_item(depth + 1)?;
+		}
+		Ok(())
+	    },
+	    Event::Tag(_) => self.skip_item(depth + 1),
+	    Event::Break => Err(Error::UnexpectedBreak),
+	    Event::End => Err(Error::UnexpectedEnd),
+	    _ => Ok(())
+	}
+    }
+
+    /// 不定長バイト列の断片をブレイクまで読み込み、連結したバイト列を返す。
+    ///
+    /// 直前に`IndefiniteByteString`を取得している必要がある。
+    #[cfg(feature = "alloc")]
+    pub fn read_indefinite_bytes(&mut self) -> Result<Vec<u8>> {
+	self.read_chunks(0x40)
+    }
+
+    /// 不定長文字列の断片をブレイクまで読み込み、連結した文字列を返す。
+    ///
+    /// 直前に`IndefiniteTextString`を取得している必要がある。
+    /// UTF-8の検査は連結した文字列全体に対して行うため、複数バイトの文字が断片の境界で分割されていても受け入れる。
+    #[cfg(feature = "alloc")]
+    pub fn read_indefinite_text(&mut self) -> Result<String> {
+	String::from_utf8(self.read_chunks(0x60)?).map_err(|_| Error::InvalidUtf8)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn read_chunks(&mut self, chunk_type: u8) -> Result<Vec<u8>> {
+	if self.chunk_type != Some(chunk_type) {
+	    return Err(Error::InvalidChunk);
+	}
+
+	let mut buf = Vec::new();
+
+	loop {
+	    match self.decode_event()? {
+		Event::ByteString(chunk) | Event::TextString(chunk) => buf.extend_from_slice(chunk),
+		Event::Break => return Ok(buf),
+		_ => return Err(Error::UnexpectedEnd)
+	    }
+	}
+    }
+
+    fn skip_break(&mut self) -> Result<bool> {
+	match self.peek_event()? {
+	    Event::Break => {
+		self.decode_event()?;
+		Ok(true)
+	    },
+	    Event::End => Err(Error::UnexpectedEnd),
+	    _ => Ok(false)
+	}
+    }
+    
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Clone, Copy, Debug)]
+enum Frame {
+    Definite(u64),
+    Indefinite,
+    IndefiniteMap(bool)
+}
+
+/// 入れ子になった配列・マップ・タグの構造を追跡しながらデコードするデコーダー型。
+///
+/// 定長の配列・マップやタグの要素が来るべき位置にブレイクが現れた場合や、不定長マップのキーの直後にブレイクが現れた場合は`UnexpectedBreak`エラーとする。
+/// データ項目の途中でバイト列が終わった場合は`UnexpectedEnd`エラーとする。
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct ContainerDecoder<'a> {
+    decoder: Decoder<'a>,
+    stack: Vec<Frame>
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> ContainerDecoder<'a> {
+
+    /// デコーダーを作成する。パラメーターはデコード対象のバイト列。
+    pub fn new(data: &'a [u8]) -> ContainerDecoder<'a> {
+	ContainerDecoder {
+	    decoder: Decoder::new(data),
+	    stack: Vec::new()
+	}
+    }
+
+    /// まだデコードされていない残りのバイト列を取得する。
+    pub fn remaining(&self) -> &'a [u8] {
+	self.decoder.remaining()
+    }
+
+    /// 現在の入れ子の深さを返す。要素の途中にある配列・マップ・タグ・不定長文字列の数で、最上位では0となる。
+    pub fn depth(&self) -> usize {
+	self.stack.len()
+    }
+
+    /// 次のイベントを取得する。
+    pub fn decode_event(&mut self) -> Result<Event<'a>> {
+	let event = self.decoder.peek_event()?;
+
+	match event {
+	    Event::Break => match self.stack.last() {
+		Some(Frame::Indefinite) | Some(Frame::IndefiniteMap(true)) => {
+		    self.stack.pop();
+		    self.complete_item();
+		},
+		_ => return Err(Error::UnexpectedBreak)
+	    },
+	    Event::End => if !self.stack.is_empty() {
+		return Err(Error::UnexpectedEnd);
+	    },
+	    Event::Array(len) if len > 0 => self.push(Frame::Definite(len))?,
+	    Event::Map(len) if len > 0 => self.push(Frame::Definite(len.saturating_mul(2)))?,
+	    Event::Tag(_) => self.push(Frame::Definite(1))?,
+	    Event::IndefiniteByteString | Event::IndefiniteTextString | Event::IndefiniteArray => self.push(Frame::Indefinite)?,
+	    Event::IndefiniteMap => self.push(Frame::IndefiniteMap(true))?,
+	    _ => self.complete_item()
+	}
+
+	self.decoder.decode_event()
+    }
+
+    fn push(&mut self, frame: Frame) -> Result<()> {
+	if self.stack.len() >= MAX_NESTING_DEPTH {
+	    return Err(Error::NestingTooDeep);
+	}
+
+	self.stack.push(frame);
+	Ok(())
+    }
+
+    fn complete_item(&mut self) {
+	while let Some(frame) = self.stack.last_mut() {
+	    match frame {
+		Frame::Definite(1) => {
+		    self.stack.pop();
+		},
+		Frame::Definite(remaining) => {
+		    *remaining -= 1;
+		    return;
+		},
+		Frame::Indefinite => return,
+		Frame::IndefiniteMap(key_next) => {
+		    *key_next = !*key_next;
+		    return;
+		}
+	    }
+	}
+    }
+
+}
+
+/// 定長配列の要素を順に返すイテレーター型。`Decoder::array_items`で作成する。
+///
+/// エラーが発生した場合はそのエラーを返した後に終了する。
+pub struct ArrayItems<'d, 'a> {
+    decoder: &'d mut Decoder<'a>,
+    remaining: u64
+}
+
+impl<'d, 'a> Iterator for ArrayItems<'d, 'a> {
+    type Item = Result<Decoder<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+	if self.remaining == 0 {
+	    return None;
+	}
+
+	match self.decoder.next_item() {
+	    Ok(item) => {
+		self.remaining -= 1;
+		Some(Ok(item))
+	    },
+	    Err(err) => {
+		self.remaining = 0;
+		Some(Err(err))
+	    }
+	}
+    }
+}
+
+/// 配列の要素を読み込むスコープ型。`Decoder::expect_array`で作成する。
+///
+/// 宣言された数より多く要素を読もうとした場合や、要素が残っているのに`end`を呼び出した場合は`ElementCountMismatch`を返す。
+/// `end`を呼び出さずに破棄した場合は何も検査しない。
+pub struct ArrayScope<'d, 'a> {
+    decoder: &'d mut Decoder<'a>,
+    len: Option<u64>,
+    remaining: Option<u64>
+}
+
+impl<'d, 'a> ArrayScope<'d, 'a> {
+
+    /// 宣言された配列長を返す。不定長配列の場合は`None`を返す。
+    pub fn declared_len(&self) -> Option<u64> {
+	self.len
+    }
+
+    /// まだ読んでいない要素があれば`true`を返す。
+    pub fn has_next(&self) -> Result<bool> {
+	match self.remaining {
+	    Some(remaining) => Ok(remaining > 0),
+	    None => Ok(self.decoder.peek_event()? != Event::Break)
+	}
+    }
+
+    /// 次の要素を1つのデータ項目だけを含むデコーダーとして返す。
+    pub fn next_element(&mut self) -> Result<Decoder<'a>> {
+	if !self.has_next()? {
+	    return Err(Error::ElementCountMismatch);
+	}
+
+	let item = self.decoder.next_item()?;
+	if let Some(remaining) = self.remaining.as_mut() {
+	    *remaining -= 1;
+	}
+
+	Ok(item)
+    }
+
+    /// 全ての要素を読んだことを検査してスコープを終える。不定長配列の場合はブレイクを消費する。
+    pub fn end(self) -> Result<()> {
+	if self.has_next()? {
+	    return Err(Error::ElementCountMismatch);
+	}
+
+	if self.remaining.is_none() {
+	    self.decoder.decode_event()?;
+	}
+
+	Ok(())
+    }
+
+}
+
+/// 定長マップのエントリーを順に返すイテレーター型。`Decoder::map_entries`で作成する。
+///
+/// エラーが発生した場合はそのエラーを返した後に終了する。
+pub struct MapEntries<'d, 'a> {
+    decoder: &'d mut Decoder<'a>,
+    remaining: u64
+}
+
+impl<'d, 'a> Iterator for MapEntries<'d, 'a> {
+    type Item = Result<(Decoder<'a>, Decoder<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+	if self.remaining == 0 {
+	    return None;
+	}
+
+	let entry = self.decoder.next_item().and_then(|key| Ok((key, self.decoder.next_item()?)));
+	if entry.is_ok() {
+	    self.remaining -= 1;
+	} else {
+	    self.remaining = 0;
+	}
+
+	Some(entry)
+    }
+}
+
+/// 定長マップの文字列のキーと値のバイト列の組を順に返すイテレーター型。`Decoder::map_str_entries`で作成する。
+///
+/// エラーが発生した場合はそのエラーを返した後に終了する。
+pub struct MapStrEntries<'d, 'a> {
+    decoder: &'d mut Decoder<'a>,
+    remaining: u64
+}
+
+impl<'d, 'a> Iterator for MapStrEntries<'d, 'a> {
+    type Item = Result<(&'a str, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+	if self.remaining == 0 {
+	    return None;
+	}
+
+	let entry = self.decoder.expect_text().and_then(|key| Ok((key, self.decoder.take_raw_item()?)));
+	if entry.is_ok() {
+	    self.remaining -= 1;
+	} else {
+	    self.remaining = 0;
+	}
+
+	Some(entry)
+    }
+}
+
+/// 長さを表す符号なし整数に続くペイロードからなるフレームを読み込む。ペイロードと残りのバイト列を返す。
+pub fn read_framed(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    match decode_event(data)? {
+	(Event::UnsignedInteger(len), rest) => decode_bytes(rest, content_len(len)?),
+	(Event::End, _) => Err(Error::UnexpectedEnd),
+	_ => Err(Error::InvalidFrameLength)
+    }
+}
+
+/// バイト列全体を`End`までデコードしてイベントの列を返す。空のバイト列の場合は空の列を返す。
+///
+/// エラーの場合は、エラーとデコードに失敗したイベントの開始位置を返す。
+#[cfg(feature = "alloc")]
+pub fn decode_all(data: &[u8]) -> result::Result<Vec<Event<'_>>, (Error, usize)> {
+    let mut events = Vec::with_capacity(data.len() / 2);
+    let mut dec = Decoder::new(data);
+
+    loop {
+	let offset = data.len() - dec.data.len();
+	match dec.decode_event() {
+	    Ok(Event::End) => return Ok(events),
+	    Ok(event) => events.push(event),
+	    Err(err) => return Err((err, offset))
+	}
+    }
+}
+
+/// バイト列を`End`までデコードしてイベントの数を返す。イベントは保持しない。空のバイト列の場合は0を返す。
+pub fn count_events(data: &[u8]) -> Result<usize> {
+    let mut dec = Decoder::new(data);
+    let mut count = 0;
+
+    while dec.decode_event()? != Event::End {
+	count += 1;
+    }
+
+    Ok(count)
+}
+
+/// バイト列全体が整形式のCBORデータであるか検査する。トップレベルのデータ項目の数を返す。
+///
+/// 空のバイト列はデータ項目が0個の列として扱い、`Ok(0)`を返す。
+pub fn validate(data: &[u8]) -> Result<usize> {
+    let mut dec = Decoder::new(data);
+    let mut count = 0;
+
+    while !dec.data.is_empty() {
+	dec.skip_value()?;
+	count += 1;
+    }
+
+    Ok(count)
+}
+    
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "alloc")]
+    use alloc::vec;
+    
+    #[test]
+    fn test_head() {
+	let head = Head::new(0x15, &[]);
+	assert_eq!(head.initial_byte, 0x15);
+	assert_eq!(head.major_type(), 0x00);
+	assert_eq!(head.additional_information(), 0x15);
+	assert_eq!(head.following_bytes, []);
+	assert!(head.is_sound());
+
+	let head = Head::new(0x58, &[0xFB]);
+	assert_eq!(head.initial_byte, 0x58);
+	assert_eq!(head.major_type(), 0x40);
+	assert_eq!(head.additional_information(), 0x18);
+	assert_eq!(head.following_bytes, [0xFB]);
+	assert!(head.is_sound());
+    }
+
+    #[test]
+    fn test_major_type() {
+	let expected = [
+	    MajorType::Unsigned,
+	    MajorType::Negative,
+	    MajorType::ByteString,
+	    MajorType::TextString,
+	    MajorType::Array,
+	    MajorType::Map,
+	    MajorType::Tag,
+	    MajorType::SimpleOrFloat
+	];
+
+	for ib in 0..=0xFF_u8 {
+	    assert_eq!(MajorType::from_initial_byte(ib), expected[(ib / 0x20) as usize]);
+	}
+
+	assert_eq!(Head::new(0x1F, &[]).major_type_enum(), MajorType::Unsigned);
+	assert_eq!(Head::new(0x20, &[]).major_type_enum(), MajorType::Negative);
+	assert_eq!(Head::new(0x5F, &[]).major_type_enum(), MajorType::ByteString);
+	assert_eq!(Head::new(0xFF, &[]).major_type_enum(), MajorType::SimpleOrFloat);
+    }
+
+    #[test]
+    fn test_head_argument() {
+	let head = Head::new(0x31, &[]);
+	assert_eq!(head.argument(), Some(0x11));
+
+	let head = Head::new(0x58, &[0xAB]);
+	assert_eq!(head.argument(), Some(0xAB));
+
+	let head = Head::new(0x79, &[0xA5, 0xE6]);
+	assert_eq!(head.argument(), Some(0xA5E6));
+
+	let head = Head::new(0x9A, &[0x98, 0x36, 0x96, 0x0D]);
+	assert_eq!(head.argument(), Some(0x9836_960D));
+
+	let head = Head::new(0xBB, &[0x53, 0x84, 0xC4, 0x60, 0xFD, 0xB0, 0x04, 0xC4]);
+	assert_eq!(head.argument(), Some(0x5384_C460_FDB0_04C4));
+
+	let head = Head::new(0xFF, &[]);
+	assert_eq!(head.argument(), None);
+    }
+
+    #[test]
+    fn test_reserved_additional_info() {
+	for major_type in 0..8_u8 {
+	    for ai in 28..=30_u8 {
+		let ib = (major_type << 5) | ai;
+		let data = [ib, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+		assert!(!Head::new(ib, &[]).is_sound());
+		assert!(!Head::new(ib, &data[1..]).is_sound());
+		assert_eq!(following_bytes_len(ib), Err(Error::ReservedAdditionalInfo));
+		assert_eq!(decode_head(&data), Err(Error::ReservedAdditionalInfo));
+		assert_eq!(decode_event(&data), Err(Error::ReservedAdditionalInfo));
+		assert_eq!(Decoder::new(&data).skip_value(), Err(Error::ReservedAdditionalInfo));
+	    }
+	}
+    }
+
+    #[test]
+    fn test_decode_head() {
+	let bytes = &[0x0C, 0x6B];
+	assert_eq!(decode_head(bytes), Ok((Head::new(0x0C, &bytes[1..1]), &bytes[1..])));
+
+	let bytes = &[0xF8, 0xDB, 0x02, 0x35];
+	assert_eq!(decode_head(bytes), Ok((Head::new(0xF8, &bytes[1..2]), &bytes[2..])));
+
+	let bytes = &[0x99, 0x78, 0x14, 0xF4, 0xC6, 0xBE];
+	assert_eq!(decode_head(bytes), Ok((Head::new(0x99, &bytes[1..3]), &bytes[3..])));
+
+	let bytes = &[0x3A, 0x14, 0xE3, 0x17, 0x19, 0x49];
+	assert_eq!(decode_head(bytes), Ok((Head::new(0x3A, &bytes[1..5]), &bytes[5..])));
+
+	let bytes = &[0xBB, 0x9E, 0x1E, 0x5F, 0xD7, 0xE3, 0xA4, 0x07, 0xE1];
+	assert_eq!(decode_head(bytes), Ok((Head::new(0xBB, &bytes[1..9]), &bytes[9..])));
+		      
+    }
+
+    #[test]
+    fn test_decode_head_err() {
+	let bytes = &[0x1C];
+	assert_eq!(decode_head(bytes), Err(Error::ReservedAdditionalInfo));
+
+	let bytes = &[0x5A, 0x00, 0x00, 0x00];
+	assert_eq!(decode_head(bytes), Err(Error::UnexpectedEnd));
+
+	assert_eq!(decode_head(&[]), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_decode_bytes() {
+	let bytes = &[0x84, 0xD8, 0xFF, 0x70];
+	assert_eq!(decode_bytes(bytes, 3), Ok((&bytes[0..3], &bytes[3..])));
+
+	let bytes = &[0x34, 0x1B];
+	assert_eq!(decode_bytes(bytes, 3), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_decode_event_integer() {
+	let mut dec = Decoder::new(&[
+	    0x0B,
+	    0x18, 0x8C,
+	    0x39, 0xB9, 0x37
+	]);
+
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(0x0B)));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(0x8C)));
+	assert_eq!(dec.decode_event(), Ok(Event::NegativeInteger(0xB937)));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+    }
+
+    #[test]
+    fn test_decode_event_integer_err() {
+	let mut dec = Decoder::new(&[0x1F]);
+	assert_eq!(dec.decode_event(), Err(Error::InvalidIndefiniteLength));
+	assert_eq!(validate(&[0x81, 0x1F]), Err(Error::InvalidIndefiniteLength));
+
+	let mut dec = Decoder::new(&[0x3F]);
+	assert_eq!(dec.decode_event(), Err(Error::InvalidIndefiniteLength));
+	assert_eq!(validate(&[0x9F, 0x3F, 0xFF]), Err(Error::InvalidIndefiniteLength));
+    }
+
+    #[test]
+    fn test_decode_event_string() {
+	let mut dec = Decoder::new(&[
+	    0x43, 0x9D, 0x1B, 0x22,
+	    0x78, 0x01, 0x4E,
+	    0x5F, 0xFF,
+	    0x7F
+	]);
+
+	assert_eq!(dec.decode_event(), Ok(Event::ByteString(&[0x9D, 0x1B, 0x22])));
+	assert_eq!(dec.decode_event(), Ok(Event::TextString(&[0x4E])));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.decode_event(), Ok(Event::Break));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteTextString));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+    }
+
+    #[test]
+    fn test_decode_event_chunk() {
+	let mut dec = Decoder::new(&[
+	    0x5F, 0x41, 0x01, 0x40, 0xFF,
+	    0x7F, 0x61, 0x61, 0xFF
+	]);
+
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.decode_event(), Ok(Event::ByteString(&[0x01])));
+	assert_eq!(dec.decode_event(), Ok(Event::ByteString(&[])));
+	assert_eq!(dec.decode_event(), Ok(Event::Break));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteTextString));
+	assert_eq!(dec.decode_event(), Ok(Event::TextString(b"a")));
+	assert_eq!(dec.decode_event(), Ok(Event::Break));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+    }
+
+    #[test]
+    fn test_decode_event_chunk_err() {
+	let mut dec = Decoder::new(&[0x5F, 0x01, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.decode_event(), Err(Error::InvalidChunk));
+
+	let mut dec = Decoder::new(&[0x5F, 0x61, 0x61, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.decode_event(), Err(Error::InvalidChunk));
+
+	let mut dec = Decoder::new(&[0x7F, 0x7F, 0xFF, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteTextString));
+	assert_eq!(dec.decode_event(), Err(Error::InvalidChunk));
+    }
+
+    #[test]
+    fn test_decode_event_array_map() {
+	let mut dec = Decoder::new(&[
+	    0x83, 0x9F, 0xAC, 0xBF
+	]);
+
+	assert_eq!(dec.decode_event(), Ok(Event::Array(0x03)));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteArray));
+	assert_eq!(dec.decode_event(), Ok(Event::Map(0x0C)));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteMap));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+    }
+
+    #[test]
+    fn test_decode_event_tag() {
+	let mut dec = Decoder::new(&[0xD9, 0x5E, 0xD2]);
+
+	assert_eq!(dec.decode_event(), Ok(Event::Tag(0x5ED2)));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+    }
+
+    #[test]
+    fn test_decode_event_tag_err() {
+	let mut dec = Decoder::new(&[0xDF]);
+	assert_eq!(dec.decode_event(), Err(Error::InvalidIndefiniteLength));
+
+	let mut dec = Decoder::new(&[0xDE]);
+	assert_eq!(dec.decode_event(), Err(Error::ReservedAdditionalInfo));
+
+	let mut dec = Decoder::new(&[0xDD]);
+	assert_eq!(dec.decode_event(), Err(Error::ReservedAdditionalInfo));
+
+	let mut dec = Decoder::new(&[0xDC]);
+	assert_eq!(dec.decode_event(), Err(Error::ReservedAdditionalInfo));
+    }
+
+    #[test]
+    fn test_decode_event_simple() {
+	let mut dec = Decoder::new(&[0xE7, 0xF8, 0x5E]);
+
+	assert_eq!(dec.decode_event(), Ok(Event::Simple(0x07)));
+	assert_eq!(dec.decode_event(), Ok(Event::Simple(0x5E)));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+    }
+
+    #[test]
+    fn test_decode_event_simple_err() {
+	let mut dec = Decoder::new(&[0xF8, 20]);
+	assert_eq!(dec.decode_event(), Err(Error::InvalidSimpleValueEncoding));
+    }
+
+    #[test]
+    fn test_decode_event_float() {
+	let mut dec = Decoder::new(&[
+	    0xF9, 0x7C, 0x00,
+	    0xFA, 0x7F, 0x80, 0x00, 0x00,
+	    0xFB, 0x7F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+	]);
+
+	assert_eq!(dec.decode_event(), Ok(Event::HalfFloat(&[0x7C, 0x00])));
+	assert_eq!(dec.decode_event(), Ok(Event::SingleFloat(&[0x7F, 0x80, 0x00, 0x00])));
+	assert_eq!(dec.decode_event(), Ok(Event::DoubleFloat(&[0x7F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+    }
+
+    #[test]
+    fn test_decode_event_break() {
+	let mut dec = Decoder::new(&[0xFF]);
+
+	assert_eq!(dec.decode_event(), Ok(Event::Break));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+    }
+    
+    #[test]
+    fn test_clone() {
+	let mut dec = Decoder::new(&[0x01, 0x02]);
+	let mut fork = dec.clone();
+
+	assert_eq!(fork.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(fork.decode_event(), Ok(Event::UnsignedInteger(2)));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(fork.decode_event(), Ok(Event::End));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_debug() {
+	use alloc::format;
+
+	let dec = Decoder::new(&[0x83, 0x01, 0x02, 0x03]);
+	assert_eq!(format!("{:?}", dec), "Decoder { remaining: 4, preview: 83 01 02 03 }");
+
+	let dec = Decoder::new(&[0x00; 10]);
+	assert_eq!(format!("{:?}", dec), "Decoder { remaining: 10, preview: 00 00 00 00 00 00 00 00 .. }");
+    }
+
+    #[test]
+    fn test_try_decode_event() {
+	let first = [0x01, 0xFB, 0x40, 0x09];
+	let second = [0x21, 0xFB, 0x54, 0x44, 0x2D, 0x18];
+
+	let mut dec = Decoder::new(&first);
+	assert_eq!(dec.try_decode_event(), Ok(Some(Event::UnsignedInteger(1))));
+	assert_eq!(dec.try_decode_event(), Ok(None));
+	assert_eq!(dec.remaining(), [0xFB, 0x40, 0x09]);
+
+	let mut buf = [0_u8; 9];
+	buf[..3].copy_from_slice(dec.remaining());
+	buf[3..].copy_from_slice(&second);
+
+	let mut dec = dec.resume(&buf);
+	assert_eq!(dec.try_decode_event(), Ok(Some(Event::DoubleFloat(&[0x40, 0x09, 0x21, 0xFB, 0x54, 0x44, 0x2D, 0x18]))));
+	assert_eq!(dec.try_decode_event(), Ok(Some(Event::End)));
+    }
+
+    #[test]
+    fn test_decode_into() {
+	let mut dec = Decoder::new(&[0x01, 0x20, 0x41, 0x00, 0x61, 0x61, 0xF6, 0xF9, 0x3C, 0x00]);
+	let mut out = [Event::End; 4];
+
+	assert_eq!(dec.decode_into(&mut out), Ok(4));
+	assert_eq!(out, [Event::UnsignedInteger(1), Event::NegativeInteger(0), Event::ByteString(&[0x00]), Event::TextString(b"a")]);
+	assert_eq!(dec.decode_into(&mut out), Ok(2));
+	assert_eq!(out[..2], [Event::Simple(22), Event::HalfFloat(&[0x3C, 0x00])]);
+	assert_eq!(dec.decode_into(&mut out), Ok(0));
+
+	let mut dec = Decoder::new(&[0x01, 0x1C]);
+	assert_eq!(dec.decode_into(&mut out), Ok(1));
+	assert_eq!(dec.decode_into(&mut out), Err(Error::ReservedAdditionalInfo));
+	assert_eq!(dec.decode_into(&mut []), Ok(0));
+    }
+
+    #[test]
+    fn test_drive() {
+	struct ArrayCounter(usize);
+
+	impl EventVisitor for ArrayCounter {
+	    fn visit(&mut self, event: &Event) -> ControlFlow<()> {
+		if let Event::Array(_) | Event::IndefiniteArray = event {
+		    self.0 += 1;
+		    if self.0 == 3 {
+			return ControlFlow::Break(());
+		    }
+		}
+		ControlFlow::Continue(())
+	    }
+	}
+
+	let data = [0x82, 0x80, 0x9F, 0xFF, 0x81, 0x01];
+	let mut dec = Decoder::new(&data);
+	let mut counter = ArrayCounter(0);
+	assert_eq!(dec.drive(&mut counter), Ok(ControlFlow::Break(())));
+	assert_eq!(counter.0, 3);
+	assert_eq!(dec.position(), 3);
+
+	let mut dec = Decoder::new(&[0x82, 0x80, 0x01]);
+	let mut counter = ArrayCounter(0);
+	assert_eq!(dec.drive(&mut counter), Ok(ControlFlow::Continue(())));
+	assert_eq!(counter.0, 2);
+
+	let mut dec = Decoder::new(&[0x81, 0x1C]);
+	assert_eq!(dec.drive(&mut ArrayCounter(0)), Err(Error::ReservedAdditionalInfo));
+    }
+
+    #[test]
+    fn test_reset() {
+	let mut dec = Decoder::new(&[0x82, 0x01, 0x5F, 0x41, 0x02]);
+	assert_eq!(dec.position(), 0);
+	assert_eq!(dec.decode_event(), Ok(Event::Array(2)));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.decode_event(), Ok(Event::ByteString(&[0x02])));
+	assert_eq!(dec.position(), 5);
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+
+	dec.reset();
+	assert_eq!(dec.position(), 0);
+	assert_eq!(dec.decode_event(), Ok(Event::Array(2)));
+	assert_eq!(dec.position(), 1);
+
+	let mut dec = dec.resume(&[0x03]);
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(3)));
+	dec.reset();
+	assert_eq!(dec.remaining(), [0x03]);
+    }
+
+    #[test]
+    fn test_bookmark() {
+	let data = [0x83, 0x01, 0x7F, 0x61, 0x61, 0xFF, 0x03];
+	let mut dec = Decoder::new(&data);
+	assert_eq!(dec.decode_event(), Ok(Event::Array(3)));
+	let mark = dec.bookmark();
+	assert_eq!(mark.offset(), 1);
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteTextString));
+	let chunk = dec.bookmark();
+	assert_eq!(dec.decode_event(), Ok(Event::TextString(b"a")));
+	assert_eq!(dec.decode_event(), Ok(Event::Break));
+
+	assert_eq!(dec.seek(mark), Ok(()));
+	assert_eq!(dec.position(), 1);
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+
+	assert_eq!(dec.seek(chunk), Ok(()));
+	assert_eq!(dec.decode_event(), Ok(Event::TextString(b"a")));
+
+	let mut short = Decoder::new(&data[..2]);
+	assert_eq!(short.seek(chunk), Err(Error::SeekOutOfBounds));
+	assert_eq!(short.position(), 0);
+    }
+
+    #[test]
+    fn test_resume_chunk() {
+	let mut dec = Decoder::new(&[0x5F, 0x42, 0x01]);
+	assert_eq!(dec.try_decode_event(), Ok(Some(Event::IndefiniteByteString)));
+	assert_eq!(dec.try_decode_event(), Ok(None));
+
+	let mut dec = dec.resume(&[0x01]);
+	assert_eq!(dec.try_decode_event(), Err(Error::InvalidChunk));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_read_indefinite() {
+	let mut dec = Decoder::new(&[0x5F, 0x42, 0x61, 0x62, 0x41, 0x63, 0xFF, 0x01]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.read_indefinite_bytes(), Ok(b"abc".to_vec()));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+
+	let mut dec = Decoder::new(&[0x7F, 0x62, 0x61, 0x62, 0x60, 0x61, 0x63, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteTextString));
+	assert_eq!(dec.read_indefinite_text(), Ok(String::from("abc")));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_read_indefinite_text_split_utf8() {
+	let mut dec = Decoder::new(&[0x7F, 0x62, 0xF0, 0x9F, 0x62, 0x98, 0x80, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteTextString));
+	assert_eq!(dec.read_indefinite_text(), Ok(String::from("\u{1F600}")));
+
+	let mut dec = Decoder::new(&[0x7F, 0x62, 0xF0, 0x9F, 0x61, 0x98, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteTextString));
+	assert_eq!(dec.read_indefinite_text(), Err(Error::InvalidUtf8));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_read_indefinite_err() {
+	let mut dec = Decoder::new(&[0x5F, 0x42, 0x61, 0x62, 0x61, 0x63, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.read_indefinite_bytes(), Err(Error::InvalidChunk));
+
+	let mut dec = Decoder::new(&[0x5F, 0x42, 0x61, 0x62]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.read_indefinite_bytes(), Err(Error::UnexpectedEnd));
+
+	let mut dec = Decoder::new(&[0x5F, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.read_indefinite_text(), Err(Error::InvalidChunk));
+
+	let mut dec = Decoder::new(&[0x7F, 0x61, 0xFF, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteTextString));
+	assert_eq!(dec.read_indefinite_text(), Err(Error::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_array_items() {
+	let mut dec = Decoder::new(&[0x83, 0x01, 0x82, 0x02, 0x03, 0x04, 0x05]);
+	let mut values = [None; 3];
+
+	assert_eq!(dec.decode_event(), Ok(Event::Array(3)));
+	for (i, item) in dec.array_items(3).enumerate() {
+	    let mut item = item.unwrap();
+	    values[i] = Some(item.decode_event().unwrap());
+	}
+	assert_eq!(values, [Some(Event::UnsignedInteger(1)), Some(Event::Array(2)), Some(Event::UnsignedInteger(4))]);
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(5)));
+
+	let mut dec = Decoder::new(&[0x82, 0x82, 0x01, 0x02]);
+	assert_eq!(dec.decode_event(), Ok(Event::Array(2)));
+	let mut items = dec.array_items(2);
+	let mut item = items.next().unwrap().unwrap();
+	assert_eq!(item.remaining(), [0x82, 0x01, 0x02]);
+	assert_eq!(item.decode_event(), Ok(Event::Array(2)));
+	assert_eq!(items.next().map(|item| item.err()), Some(Some(Error::UnexpectedEnd)));
+	assert!(items.next().is_none());
+    }
+
+    #[test]
+    fn test_map_entries() {
+	let mut dec = Decoder::new(&[0xA2, 0x01, 0x02, 0x03, 0x04, 0x05]);
+	let mut entries = [None; 2];
+
+	assert_eq!(dec.decode_event(), Ok(Event::Map(2)));
+	for (i, entry) in dec.map_entries(2).enumerate() {
+	    let (mut key, mut value) = entry.unwrap();
+	    entries[i] = Some((key.decode_event().unwrap(), value.decode_event().unwrap()));
+	}
+	assert_eq!(entries, [
+	    Some((Event::UnsignedInteger(1), Event::UnsignedInteger(2))),
+	    Some((Event::UnsignedInteger(3), Event::UnsignedInteger(4)))
+	]);
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(5)));
+
+	let mut dec = Decoder::new(&[0xA2, 0x81, 0x01, 0xA1, 0x02, 0x03, 0x04]);
+	assert_eq!(dec.decode_event(), Ok(Event::Map(2)));
+	let mut entries = dec.map_entries(2);
+	let (key, value) = entries.next().unwrap().unwrap();
+	assert_eq!(key.remaining(), [0x81, 0x01]);
+	assert_eq!(value.remaining(), [0xA1, 0x02, 0x03]);
+	assert_eq!(entries.next().map(|entry| entry.err()), Some(Some(Error::UnexpectedEnd)));
+	assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn test_array_items_float_conversion() {
+	let mut dec = Decoder::with_float_conversion(&[0x82, 0xF9, 0x3C, 0x00, 0x81, 0xFA, 0x3F, 0xC0, 0x00, 0x00]);
+	assert_eq!(dec.decode_event(), Ok(Event::Array(2)));
+
+	let mut items = dec.array_items(2);
+	let mut item = items.next().unwrap().unwrap();
+	assert_eq!(item.decode_event(), Ok(Event::Float(1.0)));
+	let mut item = items.next().unwrap().unwrap();
+	assert_eq!(item.decode_event(), Ok(Event::Array(1)));
+	assert_eq!(item.decode_event(), Ok(Event::Float(1.5)));
+	assert!(items.next().is_none());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_array_items_coalesce() {
+	let mut dec = Decoder::new(&[0x81, 0x7F, 0x61, 0x61, 0x61, 0x62, 0xFF]);
+	dec.coalesce_strings(true);
+	assert_eq!(dec.decode_event(), Ok(Event::Array(1)));
+
+	let mut item = dec.array_items(1).next().unwrap().unwrap();
+	assert_eq!(item.decode_owned_event(), Ok(OwnedEvent::TextString(b"ab".to_vec())));
+    }
+
+    #[test]
+    fn test_array_items_huge_length() {
+	let mut dec = Decoder::new(&[0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x02]);
+	let Ok(Event::Array(len)) = dec.decode_event() else { panic!() };
+	assert_eq!(len, u64::MAX);
+
+	let mut items = dec.array_items(len);
+	assert!(items.next().is_some_and(|item| item.is_ok()));
+	assert!(items.next().is_some_and(|item| item.is_ok()));
+	assert_eq!(items.next().map(|item| item.err()), Some(Some(Error::UnexpectedEnd)));
+	assert!(items.next().is_none());
+    }
+
+    #[test]
+    fn test_expect_array() {
+	let mut dec = Decoder::new(&[0x83, 0x01, 0x82, 0x02, 0x03, 0x61, 0x61, 0xF6]);
+	let mut scope = dec.expect_array().unwrap();
+	assert_eq!(scope.declared_len(), Some(3));
+	assert_eq!(scope.next_element().and_then(|mut item| item.expect_u64()), Ok(1));
+	assert_eq!(scope.next_element().map(|item| item.remaining()), Ok(&[0x82, 0x02, 0x03][..]));
+	assert_eq!(scope.end(), Err(Error::ElementCountMismatch));
+
+	let mut dec = Decoder::new(&[0x82, 0x01, 0x02, 0xF6]);
+	let mut scope = dec.expect_array().unwrap();
+	assert!(scope.next_element().is_ok());
+	assert!(scope.next_element().is_ok());
+	assert_eq!(scope.has_next(), Ok(false));
+	assert!(matches!(scope.next_element(), Err(Error::ElementCountMismatch)));
+	assert_eq!(scope.end(), Ok(()));
+	assert_eq!(dec.decode_event(), Ok(Event::Simple(22)));
+
+	let mut dec = Decoder::new(&[0x9F, 0x01, 0x02, 0xFF, 0xF6]);
+	let mut scope = dec.expect_array().unwrap();
+	assert_eq!(scope.declared_len(), None);
+	assert!(scope.next_element().is_ok());
+	assert_eq!(scope.has_next(), Ok(true));
+	assert!(scope.next_element().is_ok());
+	assert!(matches!(scope.next_element(), Err(Error::ElementCountMismatch)));
+	assert_eq!(scope.end(), Ok(()));
+	assert_eq!(dec.decode_event(), Ok(Event::Simple(22)));
+
+	let mut dec = Decoder::new(&[0x9F, 0x01, 0xFF]);
+	assert_eq!(dec.expect_array().and_then(|scope| scope.end()), Err(Error::ElementCountMismatch));
+
+	let mut dec = Decoder::new(&[0xA0]);
+	assert!(matches!(dec.expect_array(), Err(Error::TypeMismatch { .. })));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_array_item_ranges() {
+	let data = [0xF6, 0x83, 0x01, 0x82, 0x02, 0x03, 0x62, 0x68, 0x69, 0x04];
+	let mut dec = Decoder::new(&data);
+	assert_eq!(dec.decode_event(), Ok(Event::Simple(22)));
+
+	let ranges = dec.array_item_ranges().unwrap();
+	assert_eq!(ranges, [2..3, 3..6, 6..9]);
+	assert_eq!(ranges.iter().flat_map(|range| &data[range.clone()]).copied().collect::<Vec<_>>(), data[2..9]);
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(4)));
+
+	let data = [0x9F, 0x01, 0x81, 0x02, 0xFF];
+	let mut dec = Decoder::new(&data);
+	assert_eq!(dec.array_item_ranges(), Ok(vec![1..2, 2..4]));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+
+	let mut dec = Decoder::new(&[0x83, 0x01, 0x02]);
+	assert_eq!(dec.array_item_ranges(), Err(Error::UnexpectedEnd));
+	assert_eq!(dec.position(), 0);
+    }
+
+    #[test]
+    fn test_map_str_entries() {
+	let mut dec = Decoder::new(&[0xA2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x82, 0x02, 0x03, 0xF6]);
+	assert_eq!(dec.decode_event(), Ok(Event::Map(2)));
+	let mut entries = dec.map_str_entries(2);
+	assert_eq!(entries.next(), Some(Ok(("a", &[0x01][..]))));
+	assert_eq!(entries.next(), Some(Ok(("b", &[0x82, 0x02, 0x03][..]))));
+	assert_eq!(entries.next(), None);
+	assert_eq!(dec.decode_event(), Ok(Event::Simple(22)));
+
+	let mut dec = Decoder::new(&[0xA2, 0x61, 0x61, 0x01, 0x02, 0x03]);
+	assert_eq!(dec.decode_event(), Ok(Event::Map(2)));
+	let mut entries = dec.map_str_entries(2);
+	assert_eq!(entries.next(), Some(Ok(("a", &[0x01][..]))));
+	assert_eq!(entries.next(), Some(Err(Error::TypeMismatch { expected: "text string", found: "unsigned integer" })));
+	assert_eq!(entries.next(), None);
+    }
+
+    #[test]
+    fn test_skip_self_describe() {
+	let mut dec = Decoder::new(&[0xD9, 0xD9, 0xF7, 0x01]);
+	assert!(dec.skip_self_describe());
+	assert!(!dec.skip_self_describe());
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+
+	let mut dec = Decoder::new(&[0xD9, 0xD9, 0xF8, 0x01]);
+	assert!(!dec.skip_self_describe());
+	assert_eq!(dec.decode_event(), Ok(Event::Tag(55800)));
+
+	let mut dec = Decoder::new(&[0xD9, 0xD9]);
+	assert!(!dec.skip_self_describe());
+	assert_eq!(dec.remaining(), [0xD9, 0xD9]);
+    }
+
+    #[test]
+    fn test_decode_event_length_overflow() {
+	let data = [0x5B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+
+	if usize::BITS == 32 {
+	    assert_eq!(decode_event(&data), Err(Error::LengthOverflow { len: 0x1_0000_0000 }));
+	} else {
+	    assert_eq!(decode_event(&data), Err(Error::UnexpectedEnd));
+	}
+
+	let data = [0x7B, 0x00, 0x00, 0x00, 0x01, 0x2A, 0x05, 0xF2, 0x00, 0x61];
+	if usize::BITS == 32 {
+	    assert_eq!(decode_event(&data), Err(Error::LengthOverflow { len: 5_000_000_000 }));
+	} else {
+	    assert_eq!(decode_event(&data), Err(Error::UnexpectedEnd));
+	}
+
+	assert_eq!(content_len(u32::MAX as u64), Ok(u32::MAX as usize));
+	assert_eq!(checked_len(5_000_000_000, u32::MAX as usize), Err(Error::LengthOverflow { len: 5_000_000_000 }));
+	assert_eq!(checked_len(u32::MAX as u64, u32::MAX as usize), Ok(u32::MAX as usize));
+    }
+
+    #[test]
+    fn test_read_framed() {
+	let data = [0x02, 0x82, 0x01, 0x01, 0xFF];
+	assert_eq!(read_framed(&data), Ok((&data[1..3], &data[3..])));
+	assert_eq!(read_framed(&data[3..]), Ok((&data[4..], &data[5..])));
+
+	assert_eq!(read_framed(&[0x03, 0x01, 0x02]), Err(Error::UnexpectedEnd));
+	assert_eq!(read_framed(&[0x41, 0x01]), Err(Error::InvalidFrameLength));
+	assert_eq!(read_framed(&[]), Err(Error::UnexpectedEnd));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_coalesce_strings() {
+	let data = [0x82, 0x7F, 0x61, 0x61, 0x61, 0x62, 0xFF, 0x5F, 0x41, 0x01, 0xFF];
+
+	let mut dec = Decoder::new(&data);
+	dec.coalesce_strings(true);
+	assert_eq!(dec.decode_owned_event(), Ok(OwnedEvent::Array(2)));
+	assert_eq!(dec.decode_owned_event(), Ok(OwnedEvent::TextString(b"ab".to_vec())));
+	assert_eq!(dec.decode_owned_event(), Ok(OwnedEvent::ByteString(vec![0x01])));
+	assert_eq!(dec.decode_owned_event(), Ok(OwnedEvent::End));
+
+	let mut dec = Decoder::new(&data);
+	assert_eq!(dec.decode_owned_event(), Ok(OwnedEvent::Array(2)));
+	assert_eq!(dec.decode_owned_event(), Ok(OwnedEvent::IndefiniteTextString));
+	assert_eq!(dec.decode_owned_event(), Ok(OwnedEvent::TextString(b"a".to_vec())));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_coalesce_strings_split_utf8() {
+	let mut dec = Decoder::new(&[0x7F, 0x61, 0xE3, 0x62, 0x81, 0x82, 0xFF]);
+	dec.coalesce_strings(true);
+	assert_eq!(dec.decode_owned_event(), Ok(OwnedEvent::TextString("\u{3042}".as_bytes().to_vec())));
+	assert_eq!(dec.decode_owned_event(), Ok(OwnedEvent::End));
+
+	let mut dec = Decoder::new(&[0x7F, 0x61, 0xE3, 0x60, 0xFF]);
+	dec.coalesce_strings(true);
+	assert_eq!(dec.decode_owned_event(), Err(Error::InvalidUtf8));
+
+	let mut dec = Decoder::new(&[0x7F, 0x62, 0xE3, 0x81, 0xFF]);
+	dec.coalesce_strings(true);
+	assert_eq!(dec.decode_owned_event(), Err(Error::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_deterministic() {
+	let mut dec = Decoder::deterministic(&[0x9F, 0x01, 0x02, 0xFF]);
+	assert_eq!(dec.peek_event(), Err(Error::IndefiniteInDeterministic));
+	assert_eq!(dec.decode_event(), Err(Error::IndefiniteInDeterministic));
+	assert_eq!(dec.remaining().len(), 4);
+
+	let mut dec = Decoder::deterministic(&[0x82, 0x01, 0x02]);
+	assert_eq!(dec.decode_event(), Ok(Event::Array(2)));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(2)));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+
+	let valid: [&[u8]; 4] = [
+	    &[0x17],
+	    &[0x18, 0x18],
+	    &[0x39, 0x01, 0x00],
+	    &[0xDA, 0x00, 0x01, 0x00, 0x00]
+	];
+	for data in valid {
+	    assert!(Decoder::deterministic(data).decode_event().is_ok());
+	}
+
+	let invalid: [&[u8]; 6] = [
+	    &[0x18, 0x17],
+	    &[0x19, 0x00, 0xFF],
+	    &[0x3A, 0x00, 0x00, 0xFF, 0xFF],
+	    &[0x98, 0x01, 0x00],
+	    &[0x58, 0x00],
+	    &[0xDB, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF]
+	];
+	for data in invalid {
+	    assert_eq!(Decoder::deterministic(data).decode_event(), Err(Error::NonCanonicalArgument));
+	    assert!(Decoder::new(data).decode_event().is_ok());
+	}
+
+	assert_eq!(Decoder::deterministic(&[0x5F, 0x40, 0xFF]).decode_event(), Err(Error::IndefiniteInDeterministic));
+	assert_eq!(Decoder::deterministic(&[0xBF, 0xFF]).skip_value(), Err(Error::IndefiniteInDeterministic));
+    }
+
+    #[test]
+    fn test_validate_tags() {
+	let mut dec = Decoder::new(&[0xC0, 0x01]);
+	dec.validate_tags(true);
+	assert_eq!(dec.decode_event(), Ok(Event::Tag(0)));
+	assert_eq!(dec.peek_event(), Err(Error::TagContentMismatch { tag: 0 }));
+	assert_eq!(dec.decode_event(), Err(Error::TagContentMismatch { tag: 0 }));
+
+	let mut dec = Decoder::new(&[0xC0, 0x61, 0x61, 0xC0, 0x01]);
+	assert_eq!(dec.skip_value(), Ok(()));
+	assert_eq!(dec.skip_value(), Ok(()));
+
+	let valid: [&[u8]; 7] = [
+	    &[0xC0, 0x61, 0x61],
+	    &[0xC1, 0x1A, 0x51, 0x4B, 0x67, 0xB0],
+	    &[0xC1, 0xF9, 0x3C, 0x00],
+	    &[0xC2, 0x41, 0x01],
+	    &[0xC3, 0x5F, 0x41, 0x01, 0xFF],
+	    &[0xD8, 0x18, 0x41, 0x01],
+	    &[0xD8, 0x20, 0x7F, 0xFF]
+	];
+	for data in valid {
+	    let mut dec = Decoder::new(data);
+	    dec.validate_tags(true);
+	    assert_eq!(dec.skip_value(), Ok(()));
+	}
+
+	let invalid: [(&[u8], u64); 6] = [
+	    (&[0xC1, 0x61, 0x61], 1),
+	    (&[0xC2, 0x01], 2),
+	    (&[0xC3, 0x61, 0x61], 3),
+	    (&[0xD8, 0x18, 0x80], 24),
+	    (&[0xD8, 0x20, 0x41, 0x01], 32),
+	    (&[0xC0, 0xC0, 0x61, 0x61], 0)
+	];
+	for (data, tag) in invalid {
+	    let mut dec = Decoder::new(data);
+	    dec.validate_tags(true);
+	    assert_eq!(dec.skip_value(), Err(Error::TagContentMismatch { tag }));
+	}
+
+	let mut dec = Decoder::new(&[0xC2, 0x41, 0x01]);
+	dec.validate_tags(true);
+	assert_eq!(dec.decode_event(), Ok(Event::Tag(2)));
+	let mut dec = dec.resume(&[0x01]);
+	assert_eq!(dec.decode_event(), Err(Error::TagContentMismatch { tag: 2 }));
+    }
+
+    #[test]
+    fn test_float_conversion() {
+	let data = [
+	    0xF9, 0x3C, 0x00,
+	    0xFA, 0x47, 0xC3, 0x50, 0x00,
+	    0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A,
+	    0xF9, 0x7E, 0x00,
+	    0x01
+	];
+	let mut dec = Decoder::with_float_conversion(&data);
+	assert_eq!(dec.peek_event(), Ok(Event::Float(1.0)));
+	assert_eq!(dec.decode_event(), Ok(Event::Float(1.0)));
+	assert_eq!(dec.decode_event(), Ok(Event::Float(100000.0)));
+	assert_eq!(dec.decode_event(), Ok(Event::Float(1.1)));
+	assert_eq!(dec.decode_event(), Ok(Event::Float(f64::NAN)));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+
+	let mut dec = Decoder::new(&data);
+	assert_eq!(dec.decode_event(), Ok(Event::HalfFloat(&[0x3C, 0x00])));
+
+	assert_ne!(Event::Float(0.0), Event::Float(-0.0));
+	assert_ne!(Event::Float(1.0), Event::HalfFloat(&[0x3C, 0x00]));
+	assert_eq!(Event::Float(1.0).encoded_len(), Some(3));
+	assert_eq!(Event::Float(1.1).encoded_len(), Some(9));
+    }
+
+    #[test]
+    fn test_require_canonical_float() {
+	let data = [
+	    0xF9, 0x3C, 0x00,
+	    0xF9, 0x7E, 0x00,
+	    0xFA, 0x47, 0xC3, 0x50, 0x00,
+	    0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A
+	];
+	let mut dec = Decoder::new(&data);
+	dec.require_canonical(true);
+	assert_eq!(dec.decode_event(), Ok(Event::HalfFloat(&[0x3C, 0x00])));
+	assert_eq!(dec.decode_event(), Ok(Event::HalfFloat(&[0x7E, 0x00])));
+	assert_eq!(dec.decode_event(), Ok(Event::SingleFloat(&[0x47, 0xC3, 0x50, 0x00])));
+	assert_eq!(dec.decode_event(), Ok(Event::DoubleFloat(&[0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A])));
+
+	let invalid: [&[u8]; 5] = [
+	    &[0xFB, 0x3F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+	    &[0xFB, 0x40, 0xF8, 0x6A, 0x00, 0x00, 0x00, 0x00, 0x00],
+	    &[0xFA, 0x3F, 0x80, 0x00, 0x00],
+	    &[0xF9, 0x7E, 0x01],
+	    &[0xFA, 0x7F, 0xC0, 0x00, 0x00]
+	];
+	for data in invalid {
+	    let mut dec = Decoder::new(data);
+	    dec.require_canonical(true);
+	    assert_eq!(dec.peek_event(), Err(Error::NonCanonicalFloat));
+	    assert_eq!(dec.decode_event(), Err(Error::NonCanonicalFloat));
+
+	    let mut dec = Decoder::new(data);
+	    assert!(dec.decode_event().is_ok());
+	}
+    }
+
+    #[test]
+    fn test_next_item_len() {
+	let mut dec = Decoder::new(&[0x82, 0x01, 0x9F, 0x02, 0x42, 0x03, 0x04, 0xFF, 0x05]);
+
+	assert_eq!(dec.next_item_len(), Ok(8));
+	assert_eq!(dec.decode_event(), Ok(Event::Array(2)));
+	assert_eq!(dec.next_item_len(), Ok(1));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(dec.next_item_len(), Ok(6));
+	assert_eq!(dec.remaining().len(), 7);
+
+	let dec = Decoder::new(&[0x82, 0x01]);
+	assert_eq!(dec.next_item_len(), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_take_raw_item() {
+	let data = [0xA1, 0x61, 0x6B, 0xC1, 0x82, 0x01, 0xF9, 0x3C, 0x00, 0x07];
+	let mut dec = Decoder::new(&data);
+
+	assert_eq!(dec.decode_event(), Ok(Event::Map(1)));
+	assert_eq!(dec.decode_event(), Ok(Event::TextString(b"k")));
+
+	let raw = dec.take_raw_item().unwrap();
+	assert_eq!(raw, &data[3..9]);
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(7)));
+
+	let mut forwarded = Decoder::new(raw);
+	let mut original = Decoder::new(&data[3..]);
+	for _ in 0..4 {
+	    assert_eq!(forwarded.decode_event(), original.decode_event());
+	}
+	assert_eq!(forwarded.decode_event(), Ok(Event::End));
+
+	let mut dec = Decoder::new(&[0x81]);
+	assert_eq!(dec.take_raw_item(), Err(Error::UnexpectedEnd));
+	assert_eq!(dec.remaining(), [0x81]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_container_decoder() {
+	let mut dec = ContainerDecoder::new(&[0x82, 0xC1, 0x01, 0xBF, 0x01, 0x9F, 0xFF, 0xFF, 0x5F, 0x41, 0x01, 0xFF]);
+
+	assert_eq!(dec.decode_event(), Ok(Event::Array(2)));
+	assert_eq!(dec.decode_event(), Ok(Event::Tag(1)));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteMap));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteArray));
+	assert_eq!(dec.decode_event(), Ok(Event::Break));
+	assert_eq!(dec.decode_event(), Ok(Event::Break));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.decode_event(), Ok(Event::ByteString(&[0x01])));
 	assert_eq!(dec.decode_event(), Ok(Event::Break));
 	assert_eq!(dec.decode_event(), Ok(Event::End));
     }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_container_decoder_depth() {
+	let mut dec = ContainerDecoder::new(&[0x82, 0x81, 0x01, 0x9F, 0xFF, 0x02]);
+	assert_eq!(dec.depth(), 0);
+
+	assert_eq!(dec.decode_event(), Ok(Event::Array(2)));
+	assert_eq!(dec.depth(), 1);
+	assert_eq!(dec.decode_event(), Ok(Event::Array(1)));
+	assert_eq!(dec.depth(), 2);
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(dec.depth(), 1);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteArray));
+	assert_eq!(dec.depth(), 2);
+	assert_eq!(dec.decode_event(), Ok(Event::Break));
+	assert_eq!(dec.depth(), 0);
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(2)));
+	assert_eq!(dec.depth(), 0);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_container_decoder_err() {
+	let mut dec = ContainerDecoder::new(&[0x81, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::Array(1)));
+	assert_eq!(dec.decode_event(), Err(Error::UnexpectedBreak));
+	assert_eq!(dec.remaining(), [0xFF]);
+
+	let mut dec = ContainerDecoder::new(&[0xFF]);
+	assert_eq!(dec.decode_event(), Err(Error::UnexpectedBreak));
+
+	let mut dec = ContainerDecoder::new(&[0xBF, 0x01, 0xFF]);
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteMap));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(dec.decode_event(), Err(Error::UnexpectedBreak));
+
+	let mut dec = ContainerDecoder::new(&[0xC1]);
+	assert_eq!(dec.decode_event(), Ok(Event::Tag(1)));
+	assert_eq!(dec.decode_event(), Err(Error::UnexpectedEnd));
+
+	let mut dec = ContainerDecoder::new(&[0x81; MAX_NESTING_DEPTH + 1]);
+	for _ in 0..MAX_NESTING_DEPTH {
+	    assert_eq!(dec.decode_event(), Ok(Event::Array(1)));
+	}
+	assert_eq!(dec.decode_event(), Err(Error::NestingTooDeep));
+    }
+
+    #[test]
+    fn test_remaining() {
+	let mut dec = Decoder::new(&[0x01, 0x42, 0x02, 0x03]);
+
+	assert_eq!(dec.remaining(), [0x01, 0x42, 0x02, 0x03]);
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(dec.remaining(), [0x42, 0x02, 0x03]);
+	assert_eq!(dec.decode_event(), Ok(Event::ByteString(&[0x02, 0x03])));
+	assert_eq!(dec.remaining(), []);
+    }
+
+    #[test]
+    fn test_expect() {
+	let mut dec = Decoder::new(&[0x18, 0x2A, 0x38, 0x63, 0x01, 0x62, 0x68, 0x69, 0x42, 0x01, 0x02, 0xF5, 0xF4, 0x82, 0x9F]);
+	assert_eq!(dec.expect_u64(), Ok(42));
+	assert_eq!(dec.expect_i128(), Ok(-100));
+	assert_eq!(dec.expect_i128(), Ok(1));
+	assert_eq!(dec.expect_text(), Ok("hi"));
+	assert_eq!(dec.expect_bytes(), Ok(&[0x01, 0x02][..]));
+	assert_eq!(dec.expect_bool(), Ok(true));
+	assert_eq!(dec.expect_bool(), Ok(false));
+	assert_eq!(dec.expect_array_len(), Ok(Some(2)));
+	assert_eq!(dec.expect_array_len(), Ok(None));
+	assert_eq!(dec.expect_u64(), Err(Error::UnexpectedEnd));
+
+	let mut dec = Decoder::new(&[0x20, 0x61, 0x61, 0x41, 0x00, 0xF6, 0xA0, 0xF9, 0x00, 0x00, 0x62, 0xFF, 0xFF, 0xFF]);
+	assert_eq!(dec.expect_u64(), Err(Error::TypeMismatch { expected: "unsigned integer", found: "negative integer" }));
+	assert_eq!(dec.expect_i128(), Ok(-1));
+	assert_eq!(dec.expect_bytes(), Err(Error::TypeMismatch { expected: "byte string", found: "text string" }));
+	assert_eq!(dec.expect_u64(), Err(Error::TypeMismatch { expected: "unsigned integer", found: "text string" }));
+	assert_eq!(dec.expect_text(), Ok("a"));
+	assert_eq!(dec.expect_text(), Err(Error::TypeMismatch { expected: "text string", found: "byte string" }));
+	assert_eq!(dec.expect_bytes(), Ok(&[0x00][..]));
+	assert_eq!(dec.expect_bool(), Err(Error::TypeMismatch { expected: "boolean", found: "null" }));
+	assert_eq!(dec.decode_event(), Ok(Event::Simple(22)));
+	assert_eq!(dec.expect_array_len(), Err(Error::TypeMismatch { expected: "array", found: "map" }));
+	assert_eq!(dec.decode_event(), Ok(Event::Map(0)));
+	assert_eq!(dec.expect_i128(), Err(Error::TypeMismatch { expected: "integer", found: "float" }));
+	assert_eq!(dec.decode_event(), Ok(Event::HalfFloat(&[0x00, 0x00])));
+	assert_eq!(dec.expect_text(), Err(Error::InvalidUtf8));
+	assert_eq!(dec.remaining(), [0x62, 0xFF, 0xFF, 0xFF]);
+
+	let mut dec = Decoder::new(&[0xFF]);
+	assert_eq!(dec.expect_u64(), Err(Error::UnexpectedBreak));
+    }
+
+    #[test]
+    fn test_peek_event() {
+	let mut dec = Decoder::new(&[0x5F, 0x01]);
+
+	assert_eq!(dec.peek_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.decode_event(), Ok(Event::IndefiniteByteString));
+	assert_eq!(dec.peek_event(), Err(Error::InvalidChunk));
+    }
+
+    #[test]
+    fn test_skip_value() {
+	let mut dec = Decoder::new(&[
+	    0x82, 0x01, 0x9F, 0x02, 0xFF,
+	    0x03
+	]);
+
+	assert_eq!(dec.skip_value(), Ok(()));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(3)));
+	assert_eq!(dec.skip_value(), Err(Error::UnexpectedEnd));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_decode_all() {
+	let mut data = Vec::from([0x99, 0x27, 0x10]);
+	data.resize(3 + 10000, 0x01);
+
+	let events = decode_all(&data).unwrap();
+	assert_eq!(events.len(), 10001);
+	assert_eq!(events[0], Event::Array(10000));
+	assert!(events[1..].iter().all(|e| *e == Event::UnsignedInteger(1)));
+
+	assert_eq!(decode_all(&[]), Ok(Vec::new()));
+	assert_eq!(decode_all(&[0x82, 0x01, 0x19, 0x01]), Err((Error::UnexpectedEnd, 2)));
+    }
+
+    #[test]
+    fn test_count_events() {
+	assert_eq!(count_events(&[0x01, 0x18, 0x64, 0x20]), Ok(3));
+	assert_eq!(count_events(&[0x82, 0x01, 0x9F, 0xFF]), Ok(4));
+	assert_eq!(count_events(&[0x01, 0x19]), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_validate() {
+	assert_eq!(validate(&[
+	    0xA2,
+	    0x61, 0x61, 0x82, 0x01, 0x9F, 0x02, 0xFF,
+	    0x61, 0x62, 0xBF, 0x5F, 0x41, 0x00, 0xFF, 0xC1, 0xF6, 0xFF,
+	    0x7F, 0x61, 0x63, 0xFF,
+	    0xF8, 0x20
+	]), Ok(3));
+    }
+
+    #[test]
+    fn test_empty_input() {
+	assert_eq!(validate(&[]), Ok(0));
+	assert_eq!(count_events(&[]), Ok(0));
+	#[cfg(feature = "alloc")]
+	assert_eq!(decode_all(&[]), Ok(vec![]));
+
+	let mut dec = Decoder::new(&[]);
+	assert_eq!(dec.decode_into(&mut [Event::End; 2]), Ok(0));
+	assert_eq!(dec.try_decode_event(), Ok(Some(Event::End)));
+
+	struct Recorder(usize);
+
+	impl EventVisitor for Recorder {
+	    fn visit(&mut self, _event: &Event) -> ControlFlow<()> {
+		self.0 += 1;
+		ControlFlow::Continue(())
+	    }
+	}
+
+	let mut recorder = Recorder(0);
+	assert_eq!(Decoder::new(&[]).drive(&mut recorder), Ok(ControlFlow::Continue(())));
+	assert_eq!(recorder.0, 0);
+    }
+
+    #[test]
+    fn test_validate_err() {
+	assert_eq!(validate(&[0x83, 0x01, 0x02]), Err(Error::UnexpectedEnd));
+	assert_eq!(validate(&[0x9F, 0x01]), Err(Error::UnexpectedEnd));
+	assert_eq!(validate(&[0xA1, 0x01]), Err(Error::UnexpectedEnd));
+	assert_eq!(validate(&[0xC2]), Err(Error::UnexpectedEnd));
+	assert_eq!(validate(&[0x81, 0xFF]), Err(Error::UnexpectedBreak));
+	assert_eq!(validate(&[0x01, 0xFF]), Err(Error::UnexpectedBreak));
+	assert_eq!(validate(&[0x5F, 0x01, 0xFF]), Err(Error::InvalidChunk));
+	assert_eq!(validate(&[0xF8, 0x10]), Err(Error::InvalidSimpleValueEncoding));
+	assert_eq!(validate(&[0x1D]), Err(Error::ReservedAdditionalInfo));
+	assert_eq!(validate(&[0x81; 300]), Err(Error::NestingTooDeep));
+    }
+    
+    #[test]
+    fn test_decode_event_no_panic() {
+	for b in 0..=255_u8 {
+	    let _ = Decoder::new(&[b]).decode_event();
+	    let _ = validate(&[b]);
+	}
+
+	for b0 in 0..=255_u8 {
+	    for b1 in 0..=255_u8 {
+		let _ = Decoder::new(&[b0, b1]).decode_event();
+		let _ = validate(&[b0, b1]);
+	    }
+	}
+
+	let mut state = 0x2545_F491_4F6C_DD1D_u64;
+	let mut buf = [0_u8; 64];
+	for _ in 0..10000 {
+	    for b in buf.iter_mut() {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		*b = state as u8;
+	    }
+	    let len = (state >> 58) as usize;
+
+	    let mut dec = Decoder::new(&buf[..len]);
+	    while let Ok(event) = dec.decode_event() {
+		if event == Event::End {
+		    break;
+		}
+	    }
+	    let _ = validate(&buf[..len]);
+	}
+    }
     
 }
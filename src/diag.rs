@@ -0,0 +1,577 @@
+use core::fmt::Write;
+use alloc::format;
+use alloc::string::String;
+
+use crate::event::*;
+use crate::decode::*;
+use crate::misc::*;
+
+/// 診断記法でのバイト列の表記。
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BytesFormat {
+    /// `h'01ff'`のような16進表記。
+    #[default]
+    Hex,
+
+    /// `b64'Af8'`のようなパディングなしのbase64url表記。
+    Base64Url
+}
+
+/// 診断記法の出力を調整するオプション。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DiagOptions {
+    /// バイト列の表記。
+    pub bytes_format: BytesFormat,
+
+    /// 浮動小数点数の小数点以下の桁数。`None`の場合は値を表すのに必要な桁数とする。
+    pub float_precision: Option<usize>,
+
+    /// 不定長のデータ項目に`_`を付けるかどうか。
+    pub indefinite_marker: bool
+}
+
+impl Default for DiagOptions {
+    fn default() -> DiagOptions {
+	DiagOptions {
+	    bytes_format: BytesFormat::Hex,
+	    float_precision: None,
+	    indefinite_marker: true
+	}
+    }
+}
+
+struct Printer<'a> {
+    decoder: Decoder<'a>,
+    output: String,
+    options: DiagOptions,
+    pretty: bool,
+    indent: usize
+}
+
+pub(crate) fn write_text(output: &mut String, text: &str) {
+    output.push('"');
+    for c in text.chars() {
+	match c {
+	    '"' => output.push_str("\\\""),
+	    '\\' => output.push_str("\\\\"),
+	    '\n' => output.push_str("\\n"),
+	    '\r' => output.push_str("\\r"),
+	    '\t' => output.push_str("\\t"),
+	    c if c.is_control() => {
+		let _ = write!(output, "\\u{:04x}", c as u32);
+	    },
+	    c => output.push(c)
+	}
+    }
+    output.push('"');
+}
+
+pub(crate) fn write_bytes(output: &mut String, bytes: &[u8]) {
+    output.push_str("h'");
+    for b in bytes {
+	let _ = write!(output, "{:02x}", b);
+    }
+    output.push('\'');
+}
+
+const BASE64URL_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// バイト列をパディングなしのbase64urlで符号化して書き込む。
+pub(crate) fn write_base64url(output: &mut String, bytes: &[u8]) {
+    for chunk in bytes.chunks(3) {
+	let mut buf = [0_u8; 3];
+	buf[..chunk.len()].copy_from_slice(chunk);
+	let bits = (buf[0] as u32) << 16 | (buf[1] as u32) << 8 | buf[2] as u32;
+
+	for i in 0..=chunk.len() {
+	    let index = (bits >> (18 - 6 * i)) & 0x3F;
+	    output.push(BASE64URL_ALPHABET[index as usize] as char);
+	}
+    }
+}
+
+pub(crate) fn write_float(output: &mut String, val: f64) {
+    if val.is_nan() {
+	output.push_str("NaN");
+    } else if val.is_infinite() {
+	output.push_str(if val > 0.0 { "Infinity" } else { "-Infinity" });
+    } else {
+	let _ = write!(output, "{:?}", val);
+    }
+}
+
+impl<'a> Printer<'a> {
+
+    fn write_item(&mut self, depth: usize) -> Result<()> {
+	if depth >= MAX_NESTING_DEPTH {
+	    return Err(Error::NestingTooDeep);
+	}
+
+	match self.decoder.decode_event()? {
+	    Event::UnsignedInteger(val) => {
+		let _ = write!(self.output, "{}", val);
+	    },
+	    Event::NegativeInteger(val) => {
+		let _ = write!(self.output, "{}", -1 - (val as i128));
+	    },
+	    Event::ByteString(content) => self.write_bytes(content),
+	    Event::TextString(content) => {
+		let text = core::str::from_utf8(content).map_err(|_| Error::InvalidUtf8)?;
+		write_text(&mut self.output, text);
+	    },
+	    Event::Array(len) => {
+		self.open("[");
+		for i in 0..len {
+		    self.separate(i == 0);
+		    self.write_item(depth + 1)?;
+		}
+		self.close(len > 0, ']');
+	    },
+	    Event::Map(len) => {
+		self.open("{");
+		for i in 0..len {
+		    self.separate(i == 0);
+		    self.write_item(depth + 1)?;
+		    self.output.push_str(": ");
+		    self.write_item(depth + 1)?;
+		}
+		self.close(len > 0, '}');
+	    },
+	    Event::IndefiniteByteString | Event::IndefiniteTextString => {
+		self.open_indefinite('(');
+		let nonempty = self.write_items_until_break(depth, false)?;
+		self.close_indefinite(nonempty, ')');
+	    },
+	    Event::IndefiniteArray => {
+		self.open_indefinite('[');
+		let nonempty = self.write_items_until_break(depth, false)?;
+		self.close_indefinite(nonempty, ']');
+	    },
+	    Event::IndefiniteMap => {
+		self.open_indefinite('{');
+		let nonempty = self.write_items_until_break(depth, true)?;
+		self.close_indefinite(nonempty, '}');
+	    },
+	    Event::Tag(val) => {
+		let _ = write!(self.output, "{}(", val);
+		self.write_item(depth + 1)?;
+		self.output.push(')');
+	    },
+	    Event::Simple(SIMPLE_VALUE_FALSE) => self.output.push_str("false"),
+	    Event::Simple(SIMPLE_VALUE_TRUE) => self.output.push_str("true"),
+	    Event::Simple(SIMPLE_VALUE_NULL) => self.output.push_str("null"),
+	    Event::Simple(SIMPLE_VALUE_UNDEFINED) => self.output.push_str("undefined"),
+	    Event::Simple(val) => {
+		let _ = write!(self.output, "simple({})", val);
+	    },
+	    Event::HalfFloat(bytes) => self.write_float(half_to_f64(bytes)),
+	    Event::SingleFloat(bytes) => self.write_float(f32::from_be_bytes(*bytes) as f64),
+	    Event::DoubleFloat(bytes) => self.write_float(f64::from_be_bytes(*bytes)),
+	    Event::Float(val) => self.write_float(val),
+	    Event::Break => return Err(Error::UnexpectedBreak),
+	    Event::End => return Err(Error::UnexpectedEnd)
+	}
+
+	Ok(())
+    }
+
+    fn write_items_until_break(&mut self, depth: usize, pairs: bool) -> Result<bool> {
+	let mut first = true;
+
+	loop {
+	    match self.decoder.peek_event()? {
+		Event::Break => {
+		    self.decoder.decode_event()?;
+		    return Ok(!first);
+		},
+		Event::End => return Err(Error::UnexpectedEnd),
+		_ => {}
+	    }
+
+	    if self.pretty || !first {
+		self.separate(first);
+	    } else if self.options.indefinite_marker {
+		self.output.push(' ');
+	    }
+	    first = false;
+
+	    self.write_item(depth + 1)?;
+	    if pairs {
+		self.output.push_str(": ");
+		self.write_item(depth + 1)?;
+	    }
+	}
+    }
+
+    fn open(&mut self, bracket: &str) {
+	self.output.push_str(bracket);
+	self.indent += 1;
+    }
+
+    fn separate(&mut self, first: bool) {
+	if !first {
+	    self.output.push(',');
+	}
+	if self.pretty {
+	    self.output.push('\n');
+	    self.write_indent();
+	} else if !first {
+	    self.output.push(' ');
+	}
+    }
+
+    fn close(&mut self, nonempty: bool, bracket: char) {
+	self.indent -= 1;
+	if self.pretty && nonempty {
+	    self.output.push('\n');
+	    self.write_indent();
+	}
+	self.output.push(bracket);
+    }
+
+    fn open_indefinite(&mut self, bracket: char) {
+	self.output.push(bracket);
+	if self.options.indefinite_marker {
+	    self.output.push('_');
+	}
+	self.indent += 1;
+    }
+
+    fn close_indefinite(&mut self, nonempty: bool, bracket: char) {
+	if !nonempty && self.options.indefinite_marker {
+	    self.output.push(' ');
+	}
+	self.close(nonempty, bracket);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+	match self.options.bytes_format {
+	    BytesFormat::Hex => write_bytes(&mut self.output, bytes),
+	    BytesFormat::Base64Url => {
+		self.output.push_str("b64'");
+		write_base64url(&mut self.output, bytes);
+		self.output.push('\'');
+	    }
+	}
+    }
+
+    fn write_float(&mut self, val: f64) {
+	match self.options.float_precision {
+	    Some(precision) if val.is_finite() => {
+		let _ = write!(self.output, "{:.*}", precision, val);
+	    },
+	    _ => write_float(&mut self.output, val)
+	}
+    }
+
+    fn write_indent(&mut self) {
+	for _ in 0..self.indent {
+	    self.output.push_str("  ");
+	}
+    }
+
+    fn write_all(mut self) -> Result<String> {
+	let mut first = true;
+
+	while self.decoder.peek_event()? != Event::End {
+	    if !first {
+		self.output.push_str(if self.pretty { ",\n" } else { ", " });
+	    }
+	    first = false;
+
+	    self.write_item(0)?;
+	}
+
+	Ok(self.output)
+    }
+
+}
+
+struct Annotator<'a> {
+    decoder: Decoder<'a>,
+    output: String
+}
+
+fn write_hex_line(output: &mut String, depth: usize, bytes: &[u8], label: &str) {
+    for _ in 0..depth {
+	output.push_str("   ");
+    }
+    for (i, b) in bytes.iter().enumerate() {
+	if i > 0 {
+	    output.push(' ');
+	}
+	let _ = write!(output, "{:02X}", b);
+    }
+    let _ = writeln!(output, " # {}", label);
+}
+
+fn event_label(event: &Event) -> String {
+    match event {
+	Event::UnsignedInteger(val) => format!("unsigned({})", val),
+	Event::NegativeInteger(val) => format!("negative({})", -1 - (*val as i128)),
+	Event::ByteString(content) => format!("bytes({})", content.len()),
+	Event::TextString(content) => format!("text({})", content.len()),
+	Event::Array(len) => format!("array({})", len),
+	Event::Map(len) => format!("map({})", len),
+	Event::IndefiniteByteString => String::from("bytes(*)"),
+	Event::IndefiniteTextString => String::from("text(*)"),
+	Event::IndefiniteArray => String::from("array(*)"),
+	Event::IndefiniteMap => String::from("map(*)"),
+	Event::Tag(val) => format!("tag({})", val),
+	Event::Simple(SIMPLE_VALUE_FALSE) => String::from("false"),
+	Event::Simple(SIMPLE_VALUE_TRUE) => String::from("true"),
+	Event::Simple(SIMPLE_VALUE_NULL) => String::from("null"),
+	Event::Simple(SIMPLE_VALUE_UNDEFINED) => String::from("undefined"),
+	Event::Simple(val) => format!("simple({})", val),
+	Event::HalfFloat(bytes) => format!("float({:?})", half_to_f64(bytes)),
+	Event::SingleFloat(bytes) => format!("float({:?})", f32::from_be_bytes(**bytes)),
+	Event::DoubleFloat(bytes) => format!("float({:?})", f64::from_be_bytes(**bytes)),
+	Event::Float(val) => format!("float({:?})", val),
+	Event::Break => String::from("break"),
+	Event::End => String::from("end")
+    }
+}
+
+impl<'a> Annotator<'a> {
+
+    fn write_item(&mut self, depth: usize) -> Result<()> {
+	if depth >= MAX_NESTING_DEPTH {
+	    return Err(Error::NestingTooDeep);
+	}
+
+	let before = self.decoder.remaining();
+	let event = self.decoder.decode_event()?;
+	let consumed = &before[..before.len() - self.decoder.remaining().len()];
+
+	match event {
+	    Event::ByteString(content) | Event::TextString(content) => {
+		let head = &consumed[..consumed.len() - content.len()];
+		write_hex_line(&mut self.output, depth, head, &event_label(&event));
+		if !content.is_empty() {
+		    let mut label = String::new();
+		    match (event, core::str::from_utf8(content)) {
+			(Event::TextString(_), Ok(text)) => write_text(&mut label, text),
+			_ => write_bytes(&mut label, content)
+		    }
+		    write_hex_line(&mut self.output, depth + 1, content, &label);
+		}
+	    },
+	    Event::Break => return Err(Error::UnexpectedBreak),
+	    Event::End => return Err(Error::UnexpectedEnd),
+	    _ => write_hex_line(&mut self.output, depth, consumed, &event_label(&event))
+	}
+
+	match event {
+	    Event::Array(len) => {
+		for _ in 0..len {
+		    self.write_item(depth + 1)?;
+		}
+	    },
+	    Event::Map(len) => {
+		for _ in 0..len {
+		    self.write_item(depth + 1)?;
+		    self.write_item(depth + 1)?;
+		}
+	    },
+	    Event::IndefiniteByteString | Event::IndefiniteTextString | Event::IndefiniteArray => {
+		while !self.write_break(depth)? {
+		    self.write_item(depth + 1)?;
+		}
+	    },
+	    Event::IndefiniteMap => {
+		while !self.write_break(depth)? {
+		    self.write_item(depth + 1)?;
+		    self.write_item(depth + 1)?;
+		}
+	    },
+	    Event::Tag(_) => self.write_item(depth + 1)?,
+	    _ => {}
+	}
+
+	Ok(())
+    }
+
+    fn write_break(&mut self, depth: usize) -> Result<bool> {
+	match self.decoder.peek_event()? {
+	    Event::Break => {
+		let before = self.decoder.remaining();
+		self.decoder.decode_event()?;
+		write_hex_line(&mut self.output, depth, &before[..1], "break");
+		Ok(true)
+	    },
+	    Event::End => Err(Error::UnexpectedEnd),
+	    _ => Ok(false)
+	}
+    }
+
+}
+
+/// バイト列の各データ項目のヘッドに注釈を付けた16進ダンプを作成する。
+///
+/// デコードに失敗した場合は、それまでの出力の後にエラーを表す行を付け加える。
+pub fn annotate(data: &[u8]) -> String {
+    let mut annotator = Annotator {
+	decoder: Decoder::new(data),
+	output: String::new()
+    };
+
+    while !annotator.decoder.remaining().is_empty() {
+	if let Err(err) = annotator.write_item(0) {
+	    let _ = writeln!(annotator.output, "# error: {:?}", err);
+	    break;
+	}
+    }
+
+    annotator.output
+}
+
+/// バイト列をRFC 8949の診断記法に変換する。
+pub fn to_diagnostic(data: &[u8]) -> Result<String> {
+    to_diagnostic_with(data, &DiagOptions::default())
+}
+
+/// バイト列をオプションに従ってRFC 8949の診断記法に変換する。
+pub fn to_diagnostic_with(data: &[u8], options: &DiagOptions) -> Result<String> {
+    Printer {
+	decoder: Decoder::new(data),
+	output: String::new(),
+	options: *options,
+	pretty: false,
+	indent: 0
+    }.write_all()
+}
+
+/// バイト列を、配列や連想配列の要素ごとに改行して深さ1つにつき空白2つで字下げした診断記法に変換する。
+pub fn pretty_print(data: &[u8]) -> Result<String> {
+    Printer {
+	decoder: Decoder::new(data),
+	output: String::new(),
+	options: DiagOptions::default(),
+	pretty: true,
+	indent: 0
+    }.write_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_diagnostic() {
+	assert_eq!(to_diagnostic(&[
+	    0x83, 0x01, 0x02, 0xA1, 0x61, 0x61, 0xF5
+	]), Ok(String::from("[1, 2, {\"a\": true}]")));
+
+	assert_eq!(to_diagnostic(&[
+	    0x84,
+	    0x38, 0x63,
+	    0x43, 0x01, 0x02, 0xFF,
+	    0xC1, 0xFB, 0x3F, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+	    0x83, 0xF4, 0xF6, 0xF7
+	]), Ok(String::from("[-100, h'0102ff', 1(1.5), [false, null, undefined]]")));
+    }
+
+    #[test]
+    fn test_to_diagnostic_indefinite() {
+	assert_eq!(to_diagnostic(&[
+	    0xBF,
+	    0x63, 0x46, 0x75, 0x6E, 0xF5,
+	    0x63, 0x41, 0x6D, 0x74, 0x9F, 0x01, 0xF9, 0x7C, 0x00, 0xFF,
+	    0x7F, 0x62, 0x61, 0x22, 0x61, 0x0A, 0xFF, 0x5F, 0xFF,
+	    0xFF
+	]), Ok(String::from("{_ \"Fun\": true, \"Amt\": [_ 1, Infinity], (_ \"a\\\"\", \"\\n\"): (_ )}")));
+    }
+
+    #[test]
+    fn test_to_diagnostic_with() {
+	let data = [0x83, 0x43, 0x01, 0xFF, 0xFE, 0x5F, 0x41, 0x66, 0xFF, 0xFB, 0x40, 0x09, 0x21, 0xFB, 0x54, 0x44, 0x2D, 0x18];
+
+	assert_eq!(to_diagnostic_with(&data, &DiagOptions::default()), Ok(String::from("[h'01fffe', (_ h'66'), 3.141592653589793]")));
+
+	let options = DiagOptions {
+	    bytes_format: BytesFormat::Base64Url,
+	    ..DiagOptions::default()
+	};
+	assert_eq!(to_diagnostic_with(&data, &options), Ok(String::from("[b64'Af_-', (_ b64'Zg'), 3.141592653589793]")));
+
+	let options = DiagOptions {
+	    float_precision: Some(2),
+	    indefinite_marker: false,
+	    ..DiagOptions::default()
+	};
+	assert_eq!(to_diagnostic_with(&data, &options), Ok(String::from("[h'01fffe', (h'66'), 3.14]")));
+	assert_eq!(to_diagnostic_with(&[0x9F, 0xFF, 0xF9, 0x7C, 0x00], &options), Ok(String::from("[], Infinity")));
+    }
+
+    #[test]
+    fn test_pretty_print() {
+	assert_eq!(pretty_print(&[
+	    0xA2,
+	    0x61, 0x61, 0x01,
+	    0x61, 0x62, 0xA2,
+	    0x61, 0x63, 0x82, 0x02, 0xC1, 0x03,
+	    0x61, 0x64, 0xA0
+	]), Ok(String::from(concat!(
+	    "{\n",
+	    "  \"a\": 1,\n",
+	    "  \"b\": {\n",
+	    "    \"c\": [\n",
+	    "      2,\n",
+	    "      1(3)\n",
+	    "    ],\n",
+	    "    \"d\": {}\n",
+	    "  }\n",
+	    "}"
+	))));
+
+	assert_eq!(pretty_print(&[
+	    0x9F, 0x01, 0xBF, 0x61, 0x61, 0x9F, 0xFF, 0xFF, 0xFF, 0x02
+	]), Ok(String::from(concat!(
+	    "[_\n",
+	    "  1,\n",
+	    "  {_\n",
+	    "    \"a\": [_ ]\n",
+	    "  }\n",
+	    "],\n",
+	    "2"
+	))));
+
+	assert_eq!(pretty_print(&[0x82, 0x01]), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_annotate() {
+	assert_eq!(annotate(&[
+	    0x83,
+	    0x18, 0x2A,
+	    0x9F, 0x20, 0xFF,
+	    0x65, 0x68, 0x65, 0x6C, 0x6C, 0x6F
+	]), concat!(
+	    "83 # array(3)\n",
+	    "   18 2A # unsigned(42)\n",
+	    "   9F # array(*)\n",
+	    "      20 # negative(-1)\n",
+	    "   FF # break\n",
+	    "   65 # text(5)\n",
+	    "      68 65 6C 6C 6F # \"hello\"\n"
+	));
+    }
+
+    #[test]
+    fn test_annotate_err() {
+	assert_eq!(annotate(&[0x82, 0xC1, 0x01]), concat!(
+	    "82 # array(2)\n",
+	    "   C1 # tag(1)\n",
+	    "      01 # unsigned(1)\n",
+	    "# error: UnexpectedEnd\n"
+	));
+    }
+
+    #[test]
+    fn test_to_diagnostic_err() {
+	assert_eq!(to_diagnostic(&[0x82, 0x01]), Err(Error::UnexpectedEnd));
+	assert_eq!(to_diagnostic(&[0x61, 0xFF]), Err(Error::InvalidUtf8));
+	assert_eq!(to_diagnostic(&[0xFF]), Err(Error::UnexpectedBreak));
+    }
+    
+}
@@ -1,15 +1,25 @@
 
-use std::result;
+use core::fmt;
+use core::result;
+#[cfg(feature = "std")]
 use std::io;
-use std::io::Write;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 
 use crate::event::*;
+use crate::tags::{POSITIVE_BIGNUM, NEGATIVE_BIGNUM};
+use crate::misc::{EMPTY_ARRAY, EMPTY_MAP, NULL};
 
 /// エンコード時に発生するエラー。
+#[derive(Debug)]
 pub enum Error {
     /// 入出力エラー。
+    #[cfg(feature = "std")]
     IoError(io::Error),
 
+    /// `&mut [u8]`の出力先に空きが無い場合。`io::Write`のエラーは`IoError`とする。
+    BufferFull,
+
     /// バイト列や文字列の長さが 2^64 - 1 よりも大きい場合。
     TooLongString,
 
@@ -17,75 +27,186 @@ pub enum Error {
     ReservedSimpleValue
 }
 
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+	Error::IoError(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    #[cfg(feature = "std")]
+	    Error::IoError(err) => write!(f, "I/O error: {}", err),
+	    Error::BufferFull => f.write_str("output buffer is full"),
+	    Error::TooLongString => f.write_str("string is too long to encode"),
+	    Error::ReservedSimpleValue => f.write_str("reserved simple value cannot be encoded")
+	}
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+	match self {
+	    Error::IoError(err) => Some(err),
+	    _ => None
+	}
+    }
+}
+
 pub type Result<T> = result::Result<T, Error>;
 
-/// エンコーダー型。
-pub struct Encoder<W: Write> {
+/// エンコーダーの出力先を表すトレイト。
+///
+/// `&mut [u8]`・`Vec<u8>`はフィーチャーに関わらず同じ動作で実装する。`io::Write`へ出力する場合は`IoSink`で包む。
+pub trait ByteSink {
+    /// バイト列を全て書き込む。
+    fn put(&mut self, bytes: &[u8]) -> Result<()>;
+}
+
+/// 空きが足りない場合は何も書き込まずに`BufferFull`を返す。
+impl ByteSink for &mut [u8] {
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+	if bytes.len() > self.len() {
+	    return Err(Error::BufferFull);
+	}
+
+	let (head, tail) = core::mem::take(self).split_at_mut(bytes.len());
+	head.copy_from_slice(bytes);
+	*self = tail;
+
+	Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ByteSink for Vec<u8> {
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+	self.extend_from_slice(bytes);
+	Ok(())
+    }
+}
+
+impl<S: ByteSink + ?Sized> ByteSink for &mut S {
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+	(**self).put(bytes)
+    }
+}
+
+/// `io::Write`を出力先とするための型。書き込みのエラーは`IoError`として返す。
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoSink<W: io::Write> {
     writer: W
 }
 
-fn write_u8<W: Write>(writer: &mut W, byte: u8) -> Result<()> {
-    if let Err(err) = writer.write_all(&[byte]) {
-	Err(Error::IoError(err))
-    } else {
+#[cfg(feature = "std")]
+impl<W: io::Write> IoSink<W> {
+
+    /// 出力先を作成する。パラメーターは書き込み先。
+    pub fn new(writer: W) -> IoSink<W> {
+	IoSink { writer }
+    }
+
+    /// 書き込み先を取り出す。
+    pub fn into_inner(self) -> W {
+	self.writer
+    }
+
+    /// 書き込み先への参照を返す。
+    pub fn get_ref(&self) -> &W {
+	&self.writer
+    }
+
+    /// 書き込み先への可変参照を返す。
+    pub fn get_mut(&mut self) -> &mut W {
+	&mut self.writer
+    }
+
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> ByteSink for IoSink<W> {
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+	Ok(self.writer.write_all(bytes)?)
+    }
+}
+
+/// 書き込まれたバイト列を捨てて、バイト数だけを数える出力先。
+///
+/// 出力先を確保する前にエンコード結果の大きさを調べるために使う。
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CountingWriter {
+    count: usize
+}
+
+impl CountingWriter {
+
+    /// これまでに書き込まれたバイト数を返す。
+    pub fn len(&self) -> usize {
+	self.count
+    }
+
+    /// まだ何も書き込まれていなければ`true`を返す。
+    pub fn is_empty(&self) -> bool {
+	self.count == 0
+    }
+
+}
+
+impl ByteSink for CountingWriter {
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+	self.count += bytes.len();
 	Ok(())
     }
 }
 
-impl<W: Write> Encoder<W> {
+/// エンコーダー型。
+pub struct Encoder<W: ByteSink> {
+    writer: W
+}
+
+fn write_u8<W: ByteSink>(writer: &mut W, byte: u8) -> Result<()> {
+    writer.put(&[byte])
+}
+
+impl<W: ByteSink> Encoder<W> {
 
     /// エンコーダーを作成する。
     pub fn new(writer: W) -> Encoder<W> {
 	Encoder { writer }
     }
+
+    /// 出力先を取り出す。
+    pub fn into_inner(self) -> W {
+	self.writer
+    }
+
+    /// 出力先への参照を返す。
+    pub fn get_ref(&self) -> &W {
+	&self.writer
+    }
+
+    /// 出力先への可変参照を返す。エンコーダーを経由せずに書き込むと、出力がCBORデータとして不正になる場合があることに注意。
+    pub fn get_mut(&mut self) -> &mut W {
+	&mut self.writer
+    }
     
     fn encode_bytes(&mut self, bytes: &[u8]) -> Result<()> {
-	if let Err(err) = self.writer.write_all(bytes) {
-	    Err(Error::IoError(err))
-	} else {
-	    Ok(())
-	}
+	self.writer.put(bytes)
     }
     
     fn encode_head_with_argument(&mut self, major_type: u8, argument: u64) -> Result<()> {
-	if argument < 24 {
-	    write_u8(&mut self.writer, major_type | (argument as u8))
-	} else if argument <= 0xFF {
-	    self.encode_bytes(&[
-		major_type | 0x18,
-		argument as u8
-	    ])
-	} else if argument <= 0xFFFF {
-	    self.encode_bytes(&[
-		major_type | 0x19,
-		(argument >> 8) as u8,
-		argument as u8
-	    ])
-	} else if argument <= 0xFFFF_FFFF {
-	    self.encode_bytes(&[
-		major_type | 0x1A,
-		(argument >> 24) as u8,
-		(argument >> 16) as u8,
-		(argument >> 8) as u8,
-		argument as u8
-	    ])
-	} else {
-	    self.encode_bytes(&[
-		major_type | 0x1B,
-		(argument >> 56) as u8,
-		(argument >> 48) as u8,
-		(argument >> 40) as u8,
-		(argument >> 32) as u8,
-		(argument >> 24) as u8,
-		(argument >> 16) as u8,
-		(argument >> 8) as u8,
-		argument as u8
-	    ])
-	}
+	let mut buf = [0_u8; 9];
+	let len = write_head(&mut buf, major_type, argument);
+
+	self.encode_bytes(&buf[..len])
     }
 
     /// イベントをエンコードする。
-    pub fn encode_event<'a>(&mut self, event: &Event<'a>) -> Result<()> {
+    pub fn encode_event(&mut self, event: &Event) -> Result<()> {
 	use Event::*;
 	match event {
 	    UnsignedInteger(val) => self.encode_head_with_argument(0x00, *val),
@@ -109,11 +230,9 @@ impl<W: Write> Encoder<W> {
 	    IndefiniteArray => write_u8(&mut self.writer, 0x9F),
 	    IndefiniteMap => write_u8(&mut self.writer, 0xBF),
 	    Tag(val) => self.encode_head_with_argument(0xC0, *val),
-	    Simple(val) => if 24 <= *val && *val <= 31 {
-		Err(Error::ReservedSimpleValue)
-	    } else {
-		self.encode_head_with_argument(0xE0, *val as u64)
-	    },
+	    // 0〜23は初めのバイトに直接、32〜255は0xF8に続く1バイトで表す。24〜31は予約されている。
+	    Simple(24..=31) => Err(Error::ReservedSimpleValue),
+	    Simple(val) => self.encode_head_with_argument(0xE0, *val as u64),
 	    HalfFloat(val) => {
 		write_u8(&mut self.writer, 0xF9)?;
 		self.encode_bytes(*val)
@@ -125,17 +244,325 @@ impl<W: Write> Encoder<W> {
 	    DoubleFloat(val) => {
 		write_u8(&mut self.writer, 0xFB)?;
 		self.encode_bytes(*val)
-	    }
+	    },
+	    Float(val) => self.encode_f64(*val),
 	    Break => write_u8(&mut self.writer, 0xFF),
 	    End => Ok(())
 	}
     }
 
+
+    /// 符号なし整数をエンコードする。64ビットに収まらない場合は正のbignumタグと最短のバイト列で出力する。
+    pub fn encode_u128(&mut self, val: u128) -> Result<()> {
+	match u64::try_from(val) {
+	    Ok(arg) => self.encode_head_with_argument(0x00, arg),
+	    Err(_) => self.encode_bignum(POSITIVE_BIGNUM, val)
+	}
+    }
+
+    /// 符号付き整数をエンコードする。64ビットの引数で表せない場合はbignumタグと最短のバイト列で出力する。
+    pub fn encode_i128(&mut self, val: i128) -> Result<()> {
+	if val >= 0 {
+	    self.encode_u128(val as u128)
+	} else {
+	    let magnitude = (-1 - val) as u128;
+	    match u64::try_from(magnitude) {
+		Ok(arg) => self.encode_head_with_argument(0x20, arg),
+		Err(_) => self.encode_bignum(NEGATIVE_BIGNUM, magnitude)
+	    }
+	}
+    }
+
+    /// 64ビット符号付き整数をエンコードする。0以上の値は符号なし整数、負の値は負整数で出力し、`0`が`-0`になることはない。
+    pub fn encode_i64(&mut self, val: i64) -> Result<()> {
+	if val >= 0 {
+	    self.encode_head_with_argument(0x00, val as u64)
+	} else {
+	    let arg = (-1 - val) as u64;
+	    debug_assert!(arg < 1 << 63);
+	    self.encode_head_with_argument(0x20, arg)
+	}
+    }
+
+    /// 浮動小数点数を値を変えない最短の形式(半精度・単精度・倍精度)でエンコードする。
+    ///
+    /// RFC 8949 4.2.2節の決定的エンコーディングに従い、NaNはペイロードに関わらず`F9 7E 00`とする。
+    pub fn encode_f64(&mut self, val: f64) -> Result<()> {
+	let mut buf = [0_u8; 9];
+	let len = write_float(&mut buf, val);
+
+	self.encode_bytes(&buf[..len])
+    }
+
+    fn encode_bignum(&mut self, tag: u64, magnitude: u128) -> Result<()> {
+	let bytes = magnitude.to_be_bytes();
+	let skip = (magnitude.leading_zeros() / 8) as usize;
+
+	self.encode_head_with_argument(0xC0, tag)?;
+	self.encode_event(&Event::ByteString(&bytes[skip..]))
+    }
+
+    /// 不定長バイト列の開始をエンコードし、断片を書き込むためのガードを返す。
+    pub fn begin_indefinite_bytes(&mut self) -> Result<IndefiniteBytes<'_, W>> {
+	write_u8(&mut self.writer, 0x5F)?;
+	Ok(IndefiniteBytes { encoder: self })
+    }
+
+    /// 不定長文字列の開始をエンコードし、断片を書き込むためのガードを返す。
+    pub fn begin_indefinite_text(&mut self) -> Result<IndefiniteText<'_, W>> {
+	write_u8(&mut self.writer, 0x7F)?;
+	Ok(IndefiniteText { encoder: self })
+    }
+
+    /// 1文字からなる文字列をエンコードする。
+    pub fn encode_char(&mut self, c: char) -> Result<()> {
+	let mut buf = [0_u8; 4];
+	let text = c.encode_utf8(&mut buf);
+
+	self.encode_event(&Event::TextString(text.as_bytes()))
+    }
+
+    /// エンコード済みのバイト列をそのまま書き込む。`Decoder::take_raw_item`で取り出したデータ項目を埋め込む場合などに使う。
+    ///
+    /// バイト列がCBORとして正しいかどうかは検査しない。
+    pub fn write_raw(&mut self, bytes: &[u8]) -> Result<()> {
+	self.encode_bytes(bytes)
+    }
+
+    /// タグをエンコードする。続けてタグの内容となるデータ項目を1つエンコードすること。
+    pub fn encode_tag(&mut self, tag: u64) -> Result<()> {
+	self.encode_head_with_argument(0xC0, tag)
+    }
+
+    /// タグをエンコードした後、`f`を呼び出してタグの内容を書き込む。`f`はデータ項目を1つだけ書き込むこと。
+    pub fn tagged<F>(&mut self, tag: u64, f: F) -> Result<()>
+    where F: FnOnce(&mut Self) -> Result<()> {
+	self.encode_tag(tag)?;
+	f(self)
+    }
+
+    /// 要素をメモリ上にためておき、`finish`で要素数を持つ定長配列として書き込むためのガードを返す。
+    #[cfg(feature = "alloc")]
+    pub fn deferred_array(&mut self) -> DeferredArray<'_, W> {
+	DeferredArray {
+	    encoder: self,
+	    items: Encoder::new(Vec::new()),
+	    len: 0
+	}
+    }
+
+    /// 空の配列をエンコードする。
+    pub fn encode_empty_array(&mut self) -> Result<()> {
+	self.encode_bytes(&EMPTY_ARRAY)
+    }
+
+    /// 空の連想配列をエンコードする。
+    pub fn encode_empty_map(&mut self) -> Result<()> {
+	self.encode_bytes(&EMPTY_MAP)
+    }
+
+    /// nullをエンコードする。
+    pub fn encode_null(&mut self) -> Result<()> {
+	self.encode_bytes(&NULL)
+    }
+
+    /// イベントのスライスを定長配列としてエンコードする。
+    ///
+    /// 各要素は1つのイベントとしてエンコードするため、配列やマップなどの複合的なデータ項目は扱えない。
+    pub fn encode_array(&mut self, items: &[Event]) -> Result<()> {
+	self.encode_head_with_argument(0x80, items.len() as u64)?;
+
+	for item in items {
+	    self.encode_event(item)?;
+	}
+
+	Ok(())
+    }
+
+    /// キーと値のイベントの組の列を定長マップとしてエンコードする。エントリー数は`len()`から得る。
+    ///
+    /// 各キーと値は1つのイベントとしてエンコードするため、配列やマップなどの複合的なデータ項目は扱えない。
+    pub fn encode_map<'a, I>(&mut self, entries: I) -> Result<()>
+    where I: ExactSizeIterator<Item = (Event<'a>, Event<'a>)> {
+	self.encode_head_with_argument(0xA0, entries.len() as u64)?;
+
+	for (key, value) in entries {
+	    self.encode_event(&key)?;
+	    self.encode_event(&value)?;
+	}
+
+	Ok(())
+    }
+
+}
+
+/// 不定長バイト列の断片を書き込むガード型。`Encoder::begin_indefinite_bytes`で作成する。
+///
+/// 書き終えたら`finish`を呼び出してブレイクを出力すること。
+pub struct IndefiniteBytes<'a, W: ByteSink> {
+    encoder: &'a mut Encoder<W>
+}
+
+impl<'a, W: ByteSink> IndefiniteBytes<'a, W> {
+
+    /// 定長バイト列の断片を書き込む。
+    pub fn write_chunk(&mut self, chunk: &[u8]) -> Result<()> {
+	self.encoder.encode_event(&Event::ByteString(chunk))
+    }
+
+    /// ブレイクを書き込んで不定長バイト列を終える。
+    pub fn finish(self) -> Result<()> {
+	self.encoder.encode_event(&Event::Break)
+    }
+
+}
+
+/// 不定長文字列の断片を書き込むガード型。`Encoder::begin_indefinite_text`で作成する。
+///
+/// 書き終えたら`finish`を呼び出してブレイクを出力すること。
+pub struct IndefiniteText<'a, W: ByteSink> {
+    encoder: &'a mut Encoder<W>
+}
+
+impl<'a, W: ByteSink> IndefiniteText<'a, W> {
+
+    /// 定長文字列の断片を書き込む。
+    pub fn write_chunk(&mut self, chunk: &str) -> Result<()> {
+	self.encoder.encode_event(&Event::TextString(chunk.as_bytes()))
+    }
+
+    /// ブレイクを書き込んで不定長文字列を終える。
+    pub fn finish(self) -> Result<()> {
+	self.encoder.encode_event(&Event::Break)
+    }
+
 }
 
-#[cfg(test)]
+/// 要素数が後から決まる定長配列を書き込むガード型。`Encoder::deferred_array`で作成する。
+///
+/// 要素は`finish`を呼び出すまでメモリ上にためておく。`finish`を呼び出さずに破棄した場合は何も出力しない。
+#[cfg(feature = "alloc")]
+pub struct DeferredArray<'a, W: ByteSink> {
+    encoder: &'a mut Encoder<W>,
+    items: Encoder<Vec<u8>>,
+    len: u64
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, W: ByteSink> DeferredArray<'a, W> {
+
+    /// 1つの要素を追加する。`events`は1つのデータ項目を構成するイベントの列であること。
+    pub fn push(&mut self, events: &[Event]) -> Result<()> {
+	for event in events {
+	    self.items.encode_event(event)?;
+	}
+	self.len += 1;
+
+	Ok(())
+    }
+
+    /// これまでに追加した要素の数を返す。
+    pub fn len(&self) -> u64 {
+	self.len
+    }
+
+    /// 要素が1つも追加されていなければ`true`を返す。
+    pub fn is_empty(&self) -> bool {
+	self.len == 0
+    }
+
+    /// 配列の先頭部分に続けて、ためておいた要素を書き込む。
+    pub fn finish(self) -> Result<()> {
+	self.encoder.encode_head_with_argument(0x80, self.len)?;
+	self.encoder.encode_bytes(&self.items.writer)
+    }
+
+}
+
+/// ペイロードの長さを表す符号なし整数に続けてペイロードを書き込み、1つのフレームとする。
+pub fn write_framed<W: ByteSink>(writer: W, payload: &[u8]) -> Result<()> {
+    let mut enc = Encoder::new(writer);
+
+    enc.encode_event(&Event::UnsignedInteger(payload.len() as u64))?;
+    enc.encode_bytes(payload)
+}
+
+/// 小さな書き込みを内部のバッファーにまとめてから出力先に書き込むエンコーダー型。
+///
+/// 破棄する前に`flush`または`into_inner`を呼び出してバッファーの内容を出力すること。
+/// 破棄時には出力しないため、呼び出さずに破棄した場合はバッファーに残った内容が失われる。
+#[cfg(feature = "alloc")]
+pub struct BufferedEncoder<W: ByteSink> {
+    encoder: Encoder<Vec<u8>>,
+    writer: W,
+    capacity: usize
+}
+
+#[cfg(feature = "alloc")]
+impl<W: ByteSink> BufferedEncoder<W> {
+
+    const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+    /// エンコーダーを作成する。
+    pub fn new(writer: W) -> BufferedEncoder<W> {
+	BufferedEncoder::with_capacity(Self::DEFAULT_CAPACITY, writer)
+    }
+
+    /// バッファーの大きさを指定してエンコーダーを作成する。
+    pub fn with_capacity(capacity: usize, writer: W) -> BufferedEncoder<W> {
+	BufferedEncoder {
+	    encoder: Encoder::new(Vec::with_capacity(capacity)),
+	    writer,
+	    capacity
+	}
+    }
+
+    /// イベントをエンコードする。バッファーが一杯になった場合は出力先に書き込む。
+    ///
+    /// バッファーの大きさ以上の内容を持つバイト列や文字列は、バッファーを経由せずに書き込む。
+    pub fn encode_event(&mut self, event: &Event) -> Result<()> {
+	match event {
+	    Event::ByteString(content) | Event::TextString(content) if content.len() >= self.capacity => {
+		let major_type = if let Event::ByteString(_) = event { 0x40 } else { 0x60 };
+		let len = u64::try_from(content.len()).map_err(|_| Error::TooLongString)?;
+		self.encoder.encode_head_with_argument(major_type, len)?;
+		self.flush()?;
+		self.writer.put(content)
+	    },
+	    _ => {
+		self.encoder.encode_event(event)?;
+		if self.encoder.writer.len() >= self.capacity {
+		    self.flush()
+		} else {
+		    Ok(())
+		}
+	    }
+	}
+    }
+
+    /// バッファーの内容を出力先に書き込む。
+    pub fn flush(&mut self) -> Result<()> {
+	if !self.encoder.writer.is_empty() {
+	    self.writer.put(&self.encoder.writer)?;
+	    self.encoder.writer.clear();
+	}
+	Ok(())
+    }
+
+    /// バッファーの内容を書き込んでから出力先を取り出す。
+    pub fn into_inner(mut self) -> Result<W> {
+	self.flush()?;
+	Ok(self.writer)
+    }
+
+}
+
+#[cfg(all(test, feature = "alloc"))]
 mod tests {
     use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use crate::decode::Decoder;
 
     #[test]
     fn test_encode_head_with_argument() {
@@ -188,6 +615,121 @@ mod tests {
 	]);
     }
 
+    #[test]
+    fn test_encode_u128_i128() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_u128(500);
+	let _ = enc.encode_i128(-500);
+	let _ = enc.encode_u128(u64::MAX as u128);
+	let _ = enc.encode_i128(-1 - u64::MAX as i128);
+
+	assert_eq!(buf, [
+	    0x19, 0x01, 0xF4,
+	    0x39, 0x01, 0xF3,
+	    0x1B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+	    0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+	]);
+
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_u128(1 << 64);
+	let _ = enc.encode_u128(u128::MAX);
+	let _ = enc.encode_i128(i128::MIN);
+
+	let mut expected = vec![0xC2, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+	expected.extend_from_slice(&[0xC2, 0x50]);
+	expected.extend_from_slice(&[0xFF; 16]);
+	expected.extend_from_slice(&[0xC3, 0x50, 0x7F]);
+	expected.extend_from_slice(&[0xFF; 15]);
+	assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn test_encode_char() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_char('a');
+	let _ = enc.encode_char('\u{E9}');
+	let _ = enc.encode_char('\u{3042}');
+	let _ = enc.encode_char('\u{1F600}');
+
+	assert_eq!(buf, [
+	    0x61, 0x61,
+	    0x62, 0xC3, 0xA9,
+	    0x63, 0xE3, 0x81, 0x82,
+	    0x64, 0xF0, 0x9F, 0x98, 0x80
+	]);
+    }
+
+    #[test]
+    fn test_write_raw() {
+	let mut dec = Decoder::new(&[0x82, 0x01, 0x61, 0x61, 0x07]);
+	let item = dec.take_raw_item().unwrap();
+
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_event(&Event::UnsignedInteger(1));
+	let _ = enc.write_raw(item);
+	let _ = enc.encode_event(&Event::UnsignedInteger(2));
+
+	assert_eq!(buf, [0x01, 0x82, 0x01, 0x61, 0x61, 0x02]);
+
+	let mut counter = CountingWriter::default();
+	let _ = Encoder::new(&mut counter).write_raw(item);
+	assert_eq!(counter.len(), 4);
+    }
+
+    #[test]
+    fn test_encode_i64() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_i64(0);
+	let _ = enc.encode_i64(-1);
+	let _ = enc.encode_i64(23);
+	let _ = enc.encode_i64(-24);
+	let _ = enc.encode_i64(-25);
+	let _ = enc.encode_i64(i64::MAX);
+	let _ = enc.encode_i64(i64::MIN);
+
+	assert_eq!(buf, [
+	    0x00,
+	    0x20,
+	    0x17,
+	    0x37,
+	    0x38, 0x18,
+	    0x1B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+	    0x3B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+	]);
+    }
+
+    #[test]
+    fn test_encode_f64() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_f64(1.0);
+	let _ = enc.encode_f64(100000.0);
+	let _ = enc.encode_f64(1.1);
+	let _ = enc.encode_f64(f64::NAN);
+	let _ = enc.encode_f64(f64::from_bits(0x7FF0_0000_0000_0001));
+	let _ = enc.encode_f64(f64::NEG_INFINITY);
+
+	assert_eq!(buf, [
+	    0xF9, 0x3C, 0x00,
+	    0xFA, 0x47, 0xC3, 0x50, 0x00,
+	    0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A,
+	    0xF9, 0x7E, 0x00,
+	    0xF9, 0x7E, 0x00,
+	    0xF9, 0xFC, 0x00
+	]);
+    }
+
     #[test]
     fn test_encode_event_string() {
 	let mut buf = Vec::<u8>::new();
@@ -224,6 +766,78 @@ mod tests {
 	]);
     }
 
+    #[test]
+    fn test_indefinite_writer() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let mut bytes = enc.begin_indefinite_bytes().unwrap();
+	let _ = bytes.write_chunk(&[0x01, 0x02]);
+	let _ = bytes.write_chunk(&[]);
+	let _ = bytes.finish();
+
+	let mut text = enc.begin_indefinite_text().unwrap();
+	let _ = text.write_chunk("ab");
+	let _ = text.finish();
+
+	assert_eq!(buf, [
+	    0x5F, 0x42, 0x01, 0x02, 0x40, 0xFF,
+	    0x7F, 0x62, 0x61, 0x62, 0xFF
+	]);
+    }
+
+    #[test]
+    fn test_encode_empty() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_empty_array();
+	let _ = enc.encode_empty_map();
+	let _ = enc.encode_null();
+
+	assert_eq!(buf, [0x80, 0xA0, 0xF6]);
+	assert_eq!(EMPTY_ARRAY, [0x80]);
+	assert_eq!(EMPTY_MAP, [0xA0]);
+	assert_eq!(NULL, [0xF6]);
+    }
+
+    #[test]
+    fn test_encode_array() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_array(&[
+	    Event::UnsignedInteger(1),
+	    Event::UnsignedInteger(24),
+	    Event::UnsignedInteger(300)
+	]);
+
+	assert_eq!(buf, [
+	    0x83,
+	    0x01,
+	    0x18, 0x18,
+	    0x19, 0x01, 0x2C
+	]);
+    }
+
+    #[test]
+    fn test_encode_map() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let entries = [
+	    (Event::TextString(b"a"), Event::UnsignedInteger(1)),
+	    (Event::TextString(b"b"), Event::NegativeInteger(0))
+	];
+	let _ = enc.encode_map(entries.into_iter());
+
+	assert_eq!(buf, [
+	    0xA2,
+	    0x61, 0x61, 0x01,
+	    0x61, 0x62, 0x20
+	]);
+    }
+
     #[test]
     fn test_encode_event_tag() {
 	let mut buf = Vec::<u8>::new();
@@ -236,6 +850,35 @@ mod tests {
 	]);
     }
 
+    #[test]
+    fn test_encode_event_float_f64() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_event(&Event::Float(1.0));
+	let _ = enc.encode_event(&Event::Float(100000.0));
+	let _ = enc.encode_event(&Event::Float(f64::NAN));
+
+	assert_eq!(buf, [
+	    0xF9, 0x3C, 0x00,
+	    0xFA, 0x47, 0xC3, 0x50, 0x00,
+	    0xF9, 0x7E, 0x00
+	]);
+    }
+
+    #[test]
+    fn test_tagged() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.tagged(0, |enc| enc.encode_event(&Event::TextString(b"2013-03-21T20:04:00Z")));
+	let _ = enc.encode_tag(0x37A5);
+
+	assert_eq!(buf[..2], [0xC0, 0x74]);
+	assert_eq!(buf[2..22], *b"2013-03-21T20:04:00Z");
+	assert_eq!(buf[22..], [0xD9, 0x37, 0xA5]);
+    }
+
     #[test]
     fn test_encode_event_simple() {
 	let mut buf = Vec::<u8>::new();
@@ -255,11 +898,27 @@ mod tests {
 	let mut buf = Vec::<u8>::new();
 	let mut enc = Encoder::new(&mut buf);
 
-	if let Err(Error::ReservedSimpleValue) = enc.encode_event(&Event::Simple(24)) {
-	    assert!(true);
-	} else {
-	    assert!(false);
-	}
+	assert!(matches!(enc.encode_event(&Event::Simple(24)), Err(Error::ReservedSimpleValue)));
+	assert!(matches!(enc.encode_event(&Event::Simple(31)), Err(Error::ReservedSimpleValue)));
+	assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_encode_event_simple_boundary() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_event(&Event::Simple(0));
+	let _ = enc.encode_event(&Event::Simple(23));
+	let _ = enc.encode_event(&Event::Simple(32));
+	let _ = enc.encode_event(&Event::Simple(255));
+
+	assert_eq!(buf, [
+	    0xE0,
+	    0xF7,
+	    0xF8, 0x20,
+	    0xF8, 0xFF
+	]);
     }
     
     #[test]
@@ -298,4 +957,221 @@ mod tests {
 	assert_eq!(buf, []);
     }
     
+    #[test]
+    fn test_encode_fixed_buffer() {
+	let mut buf = [0_u8; 16];
+	let mut enc = Encoder::new(&mut buf[..]);
+
+	assert!(enc.encode_event(&Event::TextString(b"hello")).is_ok());
+	assert!(enc.encode_event(&Event::UnsignedInteger(0x1234)).is_ok());
+	assert!(matches!(enc.encode_event(&Event::ByteString(&[0; 12])), Err(Error::BufferFull)));
+
+	assert_eq!(buf[..9], [0x65, 0x68, 0x65, 0x6C, 0x6C, 0x6F, 0x19, 0x12, 0x34]);
+	assert_eq!(buf[9..], [0x4C, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_io_error_write_zero() {
+	struct FullWriter;
+
+	impl io::Write for FullWriter {
+	    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+		Ok(0)
+	    }
+
+	    fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	    }
+	}
+
+	let mut enc = Encoder::new(IoSink::new(FullWriter));
+	match enc.encode_event(&Event::UnsignedInteger(1)) {
+	    Err(Error::IoError(err)) => assert_eq!(err.kind(), io::ErrorKind::WriteZero),
+	    other => panic!("unexpected result: {:?}", other)
+	}
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_io_sink_fixed_buffer() {
+	let mut buf = [0_u8; 4];
+	let mut enc = Encoder::new(IoSink::new(&mut buf[..]));
+
+	assert!(enc.encode_event(&Event::UnsignedInteger(1)).is_ok());
+	assert!(matches!(enc.encode_event(&Event::TextString(b"abcd")), Err(Error::IoError(_))));
+	assert_eq!(buf, [0x01, 0x64, 0x61, 0x62]);
+    }
+    
+    #[test]
+    fn test_framed_round_trip() {
+	let payload: Vec<u8> = (0..300).map(|i| i as u8).collect();
+	let mut buf = Vec::<u8>::new();
+
+	let _ = write_framed(&mut buf, &payload);
+	let _ = write_framed(&mut buf, &[0xF6]);
+	assert_eq!(buf[..3], [0x19, 0x01, 0x2C]);
+
+	let (first, rest) = crate::decode::read_framed(&buf).unwrap();
+	assert_eq!(first, payload.as_slice());
+	let (second, rest) = crate::decode::read_framed(rest).unwrap();
+	assert_eq!(second, [0xF6]);
+	assert!(rest.is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_io_error() {
+	use std::error::Error as _;
+
+	struct FailingWriter;
+
+	impl io::Write for FailingWriter {
+	    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+		Err(io::Error::other("broken pipe"))
+	    }
+
+	    fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	    }
+	}
+
+	fn encode_twice(writer: FailingWriter) -> Result<()> {
+	    let mut enc = Encoder::new(IoSink::new(writer));
+	    enc.encode_event(&Event::UnsignedInteger(1))?;
+	    enc.encode_event(&Event::UnsignedInteger(2))
+	}
+
+	let err = encode_twice(FailingWriter).unwrap_err();
+	assert!(matches!(err, Error::IoError(_)));
+	assert_eq!(err.to_string(), "I/O error: broken pipe");
+	assert!(err.source().is_some());
+
+	let err = Error::from(io::Error::from(io::ErrorKind::WriteZero));
+	assert!(matches!(err, Error::IoError(_)));
+	assert!(err.source().is_some());
+	assert!(Error::BufferFull.source().is_none());
+	assert_eq!(Error::ReservedSimpleValue.to_string(), "reserved simple value cannot be encoded");
+    }
+
+    #[test]
+    fn test_get_ref() {
+	let mut enc = Encoder::new(Vec::<u8>::new());
+
+	let _ = enc.encode_event(&Event::Array(2));
+	let _ = enc.encode_event(&Event::UnsignedInteger(1000));
+	assert_eq!(enc.get_ref().len(), 4);
+
+	enc.get_mut().push(0x01);
+	let _ = enc.encode_event(&Event::Simple(22));
+	assert_eq!(enc.into_inner(), [0x82, 0x19, 0x03, 0xE8, 0x01, 0xF6]);
+    }
+
+    #[test]
+    fn test_counting_writer() {
+	let events = [
+	    Event::Map(2),
+	    Event::TextString(b"id"),
+	    Event::UnsignedInteger(1000),
+	    Event::TextString(b"tags"),
+	    Event::IndefiniteArray,
+	    Event::ByteString(&[0; 30]),
+	    Event::DoubleFloat(&[0x40; 8]),
+	    Event::Break
+	];
+
+	let mut enc = Encoder::new(CountingWriter::default());
+	let mut buf = Vec::<u8>::new();
+	let mut vec_enc = Encoder::new(&mut buf);
+	for event in &events {
+	    let _ = enc.encode_event(event);
+	    let _ = vec_enc.encode_event(event);
+	}
+
+	let counter = enc.into_inner();
+	assert_eq!(counter.len(), buf.len());
+	assert_eq!(counter.len(), 55);
+	assert!(CountingWriter::default().is_empty());
+    }
+
+    #[test]
+    fn test_deferred_array() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let mut array = enc.deferred_array();
+	for i in 0..4 {
+	    let _ = array.push(&[Event::UnsignedInteger(i)]);
+	}
+	let _ = array.push(&[Event::Array(2), Event::TextString(b"a"), Event::Simple(22)]);
+	assert_eq!(array.len(), 5);
+	let _ = array.finish();
+
+	let array = enc.deferred_array();
+	assert!(array.is_empty());
+	let _ = array.finish();
+
+	assert_eq!(buf, [
+	    0x85,
+	    0x00, 0x01, 0x02, 0x03,
+	    0x82, 0x61, 0x61, 0xF6,
+	    0x80
+	]);
+    }
+
+    #[test]
+    fn test_buffered_encoder() {
+	let mut enc = BufferedEncoder::with_capacity(4, Vec::new());
+
+	let _ = enc.encode_event(&Event::UnsignedInteger(1));
+	let _ = enc.encode_event(&Event::UnsignedInteger(0x1234));
+	let _ = enc.encode_event(&Event::TextString(b"abcd"));
+	let _ = enc.encode_event(&Event::ByteString(&[0x01]));
+
+	assert_eq!(enc.into_inner().unwrap(), [
+	    0x01,
+	    0x19, 0x12, 0x34,
+	    0x64, 0x61, 0x62, 0x63, 0x64,
+	    0x41, 0x01
+	]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_buffered_encoder_write_count() {
+	struct CountingWriter {
+	    writes: usize,
+	    bytes: usize
+	}
+
+	impl io::Write for CountingWriter {
+	    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.writes += 1;
+		self.bytes += buf.len();
+		Ok(buf.len())
+	    }
+
+	    fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	    }
+	}
+
+	let mut writer = CountingWriter { writes: 0, bytes: 0 };
+	let mut enc = Encoder::new(IoSink::new(&mut writer));
+	for i in 0..100_000 {
+	    let _ = enc.encode_event(&Event::UnsignedInteger(i));
+	}
+	assert_eq!(writer.writes, 100_000);
+	let unbuffered_bytes = writer.bytes;
+
+	let mut writer = CountingWriter { writes: 0, bytes: 0 };
+	let mut enc = BufferedEncoder::new(IoSink::new(&mut writer));
+	for i in 0..100_000 {
+	    let _ = enc.encode_event(&Event::UnsignedInteger(i));
+	}
+	let _ = enc.flush();
+	assert!(writer.writes < 100);
+	assert_eq!(writer.bytes, unbuffered_bytes);
+    }
+    
 }
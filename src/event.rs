@@ -1,6 +1,11 @@
+use core::cmp::Ordering;
+use crate::misc::{f64_to_half, half_to_f64, SimpleValue};
+use crate::raw::minimal_arg_width;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 
 /// イベント型。
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum Event<'a> {
     /// 符号なし整数イベント。
     UnsignedInteger(u64),
@@ -47,6 +52,11 @@ pub enum Event<'a> {
     /// 倍精度浮動小数点数イベント。
     DoubleFloat(&'a [u8; 8]),
 
+    /// 値に変換した浮動小数点数イベント。`Decoder::with_float_conversion`で作成したデコーダーが返す。
+    ///
+    /// エンコードする場合は値を変えない最短の形式とし、NaNは`F9 7E 00`とする。
+    Float(f64),
+
     /// ブレイクイベント。
     Break,
 
@@ -54,4 +64,555 @@ pub enum Event<'a> {
     End
 }
 
+/// `Float`はビット列で比較する。そのため`Float(f64::NAN)`は自身と等しく、`Float(0.0)`と`Float(-0.0)`は等しくない。
+impl<'a, 'b> PartialEq<Event<'b>> for Event<'a> {
+    fn eq(&self, other: &Event<'b>) -> bool {
+	use Event::*;
+	match (self, other) {
+	    (UnsignedInteger(a), UnsignedInteger(b)) | (NegativeInteger(a), NegativeInteger(b)) => a == b,
+	    (ByteString(a), ByteString(b)) | (TextString(a), TextString(b)) => a == b,
+	    (Array(a), Array(b)) | (Map(a), Map(b)) | (Tag(a), Tag(b)) => a == b,
+	    (Simple(a), Simple(b)) => a == b,
+	    (HalfFloat(a), HalfFloat(b)) => a == b,
+	    (SingleFloat(a), SingleFloat(b)) => a == b,
+	    (DoubleFloat(a), DoubleFloat(b)) => a == b,
+	    (Float(a), Float(b)) => a.to_bits() == b.to_bits(),
+	    (IndefiniteByteString, IndefiniteByteString) | (IndefiniteTextString, IndefiniteTextString)
+		| (IndefiniteArray, IndefiniteArray) | (IndefiniteMap, IndefiniteMap)
+		| (Break, Break) | (End, End) => true,
+	    _ => false
+	}
+    }
+}
+
 impl<'a> Eq for Event<'a> {}
+
+/// エンコードした場合のバイト列による正準順序(`cmp_canonical`と同じ順序)。値の構造による順序ではないことに注意。
+///
+/// `cmp_canonical`で等しいとみなされるエンコードできない単純値と`End`は、単純値の値の順、`End`を最後として区別する。
+/// 同じバイト列にエンコードされる`Float`と他の浮動小数点数イベントは`Float`を後とし、`Float`同士はビット列の順とする。
+impl<'a> Ord for Event<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+	self.cmp_canonical(other).then_with(|| match (self, other) {
+	    (Event::Simple(val), Event::Simple(other_val)) => val.cmp(other_val),
+	    (Event::Simple(_), _) => Ordering::Less,
+	    (_, Event::Simple(_)) => Ordering::Greater,
+	    (Event::Float(val), Event::Float(other_val)) => val.to_bits().cmp(&other_val.to_bits()),
+	    (Event::Float(_), _) => Ordering::Greater,
+	    (_, Event::Float(_)) => Ordering::Less,
+	    _ => Ordering::Equal
+	})
+    }
+}
+
+impl<'a> PartialOrd for Event<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+	Some(self.cmp(other))
+    }
+}
+
+fn head_len_with_argument(argument: u64) -> usize {
+    1 + minimal_arg_width(argument) as usize
+}
+
+/// 浮動小数点数を値を変えない最短の形式で書き込み、バイト数を返す。NaNは`F9 7E 00`とする。
+pub(crate) fn write_float(buf: &mut [u8; 9], val: f64) -> usize {
+    if val.is_nan() {
+	buf[..3].copy_from_slice(&[0xF9, 0x7E, 0x00]);
+	3
+    } else if let Some(bytes) = f64_to_half(val) {
+	buf[0] = 0xF9;
+	buf[1..3].copy_from_slice(&bytes);
+	3
+    } else if (val as f32) as f64 == val {
+	buf[0] = 0xFA;
+	buf[1..5].copy_from_slice(&(val as f32).to_be_bytes());
+	5
+    } else {
+	buf[0] = 0xFB;
+	buf[1..9].copy_from_slice(&val.to_be_bytes());
+	9
+    }
+}
+
+pub(crate) fn write_head(buf: &mut [u8; 9], major_type: u8, argument: u64) -> usize {
+    let width = minimal_arg_width(argument) as usize;
+
+    buf[0] = major_type | match width {
+	0 => argument as u8,
+	1 => 0x18,
+	2 => 0x19,
+	4 => 0x1A,
+	_ => 0x1B
+    };
+    buf[1..=width].copy_from_slice(&argument.to_be_bytes()[8 - width..]);
+
+    width + 1
+}
+
+impl<'a> Event<'a> {
+
+    /// イベントをエンコードした場合のバイト数を返す。`End`やエンコードできない単純値の場合は`None`を返す。
+    pub fn encoded_len(&self) -> Option<usize> {
+	use Event::*;
+	match self {
+	    UnsignedInteger(val) | NegativeInteger(val) | Array(val) | Map(val) | Tag(val) => Some(head_len_with_argument(*val)),
+	    ByteString(content) | TextString(content) => Some(head_len_with_argument(content.len() as u64) + content.len()),
+	    IndefiniteByteString | IndefiniteTextString | IndefiniteArray | IndefiniteMap | Break => Some(1),
+	    Simple(24..=31) => None,
+	    Simple(val) => Some(head_len_with_argument(*val as u64)),
+	    HalfFloat(_) => Some(3),
+	    SingleFloat(_) => Some(5),
+	    DoubleFloat(_) => Some(9),
+	    Float(val) => Some(write_float(&mut [0; 9], *val)),
+	    End => None
+	}
+    }
+
+    /// イベントをエンコードした場合の先頭部分(初めのバイトと引数)のバイト数を返す。
+    ///
+    /// 文字列の内容は含まない。エンコードできない単純値(24〜31)は引数を1バイトとして数え、`End`は0を返す。
+    pub fn head_len(&self) -> usize {
+	use Event::*;
+	match self {
+	    UnsignedInteger(val) | NegativeInteger(val) | Array(val) | Map(val) | Tag(val) => head_len_with_argument(*val),
+	    ByteString(content) | TextString(content) => head_len_with_argument(content.len() as u64),
+	    IndefiniteByteString | IndefiniteTextString | IndefiniteArray | IndefiniteMap | Break => 1,
+	    Simple(val) => head_len_with_argument(*val as u64),
+	    HalfFloat(_) => 3,
+	    SingleFloat(_) => 5,
+	    DoubleFloat(_) => 9,
+	    Float(val) => write_float(&mut [0; 9], *val),
+	    End => 0
+	}
+    }
+
+    /// `ByteString`・`TextString`であれば、先頭部分を除いた内容のバイト数を返す。その他のイベントの場合は`None`を返す。
+    pub fn content_len(&self) -> Option<usize> {
+	match self {
+	    Event::ByteString(content) | Event::TextString(content) => Some(content.len()),
+	    _ => None
+	}
+    }
+
+    /// 単純値イベントであれば、その単純値を返す。
+    pub fn simple_value(&self) -> Option<SimpleValue> {
+	match *self {
+	    Event::Simple(val) => Some(SimpleValue::from_u8(val)),
+	    _ => None
+	}
+    }
+
+    /// `HalfFloat`・`SingleFloat`・`DoubleFloat`であれば、ビッグエンディアンのバイト列を返す。
+    ///
+    /// 値を保持する`Float`はバイト列を持たないため`None`を返す。
+    pub fn float_bytes(&self) -> Option<&'a [u8]> {
+	match *self {
+	    Event::HalfFloat(bytes) => Some(bytes),
+	    Event::SingleFloat(bytes) => Some(bytes),
+	    Event::DoubleFloat(bytes) => Some(bytes),
+	    _ => None
+	}
+    }
+
+    /// イベントのメジャータイプ(0〜7)を返す。`Break`と`End`の場合は`None`を返す。
+    pub fn major_type(&self) -> Option<u8> {
+	use Event::*;
+	match self {
+	    UnsignedInteger(_) => Some(0),
+	    NegativeInteger(_) => Some(1),
+	    ByteString(_) | IndefiniteByteString => Some(2),
+	    TextString(_) | IndefiniteTextString => Some(3),
+	    Array(_) | IndefiniteArray => Some(4),
+	    Map(_) | IndefiniteMap => Some(5),
+	    Tag(_) => Some(6),
+	    Simple(_) | HalfFloat(_) | SingleFloat(_) | DoubleFloat(_) | Float(_) => Some(7),
+	    Break | End => None
+	}
+    }
+
+    /// 浮動小数点数の幅を無視して値を比較する。
+    ///
+    /// `HalfFloat`・`SingleFloat`・`DoubleFloat`・`Float`は`f64`に拡張した値で比較するため、`0.0`と`-0.0`は等しい。
+    /// NaNはIEEE 754に従ってどの値とも(NaN同士であっても)等しくない。
+    /// 整数は符号を含めた整数値で比較し、整数と浮動小数点数は値が同じでも等しくない。その他のイベントは`==`と同じ。
+    pub fn value_eq(&self, other: &Event) -> bool {
+	match (self.float_value(), other.float_value()) {
+	    (Some(val), Some(other_val)) => val == other_val,
+	    (None, None) => self == other,
+	    _ => false
+	}
+    }
+
+    fn float_value(&self) -> Option<f64> {
+	match *self {
+	    Event::HalfFloat(bytes) => Some(half_to_f64(bytes)),
+	    Event::SingleFloat(bytes) => Some(f32::from_be_bytes(*bytes) as f64),
+	    Event::DoubleFloat(bytes) => Some(f64::from_be_bytes(*bytes)),
+	    Event::Float(val) => Some(val),
+	    _ => None
+	}
+    }
+
+    fn canonical_parts(&self, buf: &mut [u8; 9]) -> Option<(usize, &'a [u8])> {
+	use Event::*;
+	let len = match *self {
+	    UnsignedInteger(val) => write_head(buf, 0x00, val),
+	    NegativeInteger(val) => write_head(buf, 0x20, val),
+	    ByteString(content) => return Some((write_head(buf, 0x40, content.len() as u64), content)),
+	    TextString(content) => return Some((write_head(buf, 0x60, content.len() as u64), content)),
+	    Array(len) => write_head(buf, 0x80, len),
+	    Map(len) => write_head(buf, 0xA0, len),
+	    Tag(val) => write_head(buf, 0xC0, val),
+	    IndefiniteByteString | IndefiniteTextString | IndefiniteArray | IndefiniteMap | Break => {
+		buf[0] = match *self {
+		    IndefiniteByteString => 0x5F,
+		    IndefiniteTextString => 0x7F,
+		    IndefiniteArray => 0x9F,
+		    IndefiniteMap => 0xBF,
+		    _ => 0xFF
+		};
+		1
+	    },
+	    Simple(24..=31) | End => return None,
+	    Simple(val) => write_head(buf, 0xE0, val as u64),
+	    HalfFloat(bytes) => {
+		buf[0] = 0xF9;
+		buf[1..3].copy_from_slice(bytes);
+		3
+	    },
+	    SingleFloat(bytes) => {
+		buf[0] = 0xFA;
+		buf[1..5].copy_from_slice(bytes);
+		5
+	    },
+	    DoubleFloat(bytes) => {
+		buf[0] = 0xFB;
+		buf[1..9].copy_from_slice(bytes);
+		9
+	    },
+	    Float(val) => write_float(buf, val)
+	};
+
+	Some((len, &[]))
+    }
+
+    /// エンコードした場合のバイト列を、RFC 8949 4.2.1節の規則(短いものが先、同じ長さなら辞書順)で比較する。
+    ///
+    /// `End`やエンコードできない単純値は、エンコードできるどのイベントよりも後になり、互いには等しいとみなす。
+    pub fn cmp_canonical(&self, other: &Event) -> Ordering {
+	let mut buf = [0_u8; 9];
+	let mut other_buf = [0_u8; 9];
+
+	match (self.canonical_parts(&mut buf), other.canonical_parts(&mut other_buf)) {
+	    (Some((head_len, content)), Some((other_head_len, other_content))) => {
+		(head_len + content.len()).cmp(&(other_head_len + other_content.len()))
+		    .then_with(|| buf[..head_len].iter().chain(content).cmp(other_buf[..other_head_len].iter().chain(other_content)))
+	    },
+	    (Some(_), None) => Ordering::Less,
+	    (None, Some(_)) => Ordering::Greater,
+	    (None, None) => Ordering::Equal
+	}
+    }
+
+}
+
+/// 内容を所有するイベント型。
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub enum OwnedEvent {
+    /// 符号なし整数イベント。
+    UnsignedInteger(u64),
+
+    /// 負整数イベント。
+    NegativeInteger(u64),
+
+    /// バイト列イベント。
+    ByteString(Vec<u8>),
+
+    /// 文字列イベント。
+    TextString(Vec<u8>),
+
+    /// 配列イベント。パラメーターは配列長。
+    Array(u64),
+
+    /// 連想配列イベント。パラメーターは連想数。
+    Map(u64),
+
+    /// 不定長バイト列イベント。
+    IndefiniteByteString,
+
+    /// 不定長文字列イベント。
+    IndefiniteTextString,
+
+    /// 不定長配列イベント。
+    IndefiniteArray,
+
+    /// 不定長連想配列イベント。
+    IndefiniteMap,
+
+    /// タグイベント。
+    Tag(u64),
+
+    /// 単純値イベント。
+    Simple(u8),
+
+    /// 半精度浮動小数点数イベント。
+    HalfFloat([u8; 2]),
+
+    /// 単精度浮動小数点数イベント。
+    SingleFloat([u8; 4]),
+
+    /// 倍精度浮動小数点数イベント。
+    DoubleFloat([u8; 8]),
+
+    /// 値に変換した浮動小数点数イベント。
+    Float(f64),
+
+    /// ブレイクイベント。
+    Break,
+
+    /// データの終端を表すイベント。
+    End
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq for OwnedEvent {
+    fn eq(&self, other: &OwnedEvent) -> bool {
+	self.as_event() == other.as_event()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Eq for OwnedEvent {}
+
+#[cfg(feature = "alloc")]
+impl OwnedEvent {
+
+    /// 内容を借用するイベントに変換する。
+    pub fn as_event(&self) -> Event<'_> {
+	match self {
+	    OwnedEvent::UnsignedInteger(val) => Event::UnsignedInteger(*val),
+	    OwnedEvent::NegativeInteger(val) => Event::NegativeInteger(*val),
+	    OwnedEvent::ByteString(content) => Event::ByteString(content),
+	    OwnedEvent::TextString(content) => Event::TextString(content),
+	    OwnedEvent::Array(len) => Event::Array(*len),
+	    OwnedEvent::Map(len) => Event::Map(*len),
+	    OwnedEvent::IndefiniteByteString => Event::IndefiniteByteString,
+	    OwnedEvent::IndefiniteTextString => Event::IndefiniteTextString,
+	    OwnedEvent::IndefiniteArray => Event::IndefiniteArray,
+	    OwnedEvent::IndefiniteMap => Event::IndefiniteMap,
+	    OwnedEvent::Tag(val) => Event::Tag(*val),
+	    OwnedEvent::Simple(val) => Event::Simple(*val),
+	    OwnedEvent::HalfFloat(bytes) => Event::HalfFloat(bytes),
+	    OwnedEvent::SingleFloat(bytes) => Event::SingleFloat(bytes),
+	    OwnedEvent::DoubleFloat(bytes) => Event::DoubleFloat(bytes),
+	    OwnedEvent::Float(val) => Event::Float(*val),
+	    OwnedEvent::Break => Event::Break,
+	    OwnedEvent::End => Event::End
+	}
+    }
+
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<Event<'a>> for OwnedEvent {
+    fn from(event: Event<'a>) -> OwnedEvent {
+	match event {
+	    Event::UnsignedInteger(val) => OwnedEvent::UnsignedInteger(val),
+	    Event::NegativeInteger(val) => OwnedEvent::NegativeInteger(val),
+	    Event::ByteString(content) => OwnedEvent::ByteString(content.to_vec()),
+	    Event::TextString(content) => OwnedEvent::TextString(content.to_vec()),
+	    Event::Array(len) => OwnedEvent::Array(len),
+	    Event::Map(len) => OwnedEvent::Map(len),
+	    Event::IndefiniteByteString => OwnedEvent::IndefiniteByteString,
+	    Event::IndefiniteTextString => OwnedEvent::IndefiniteTextString,
+	    Event::IndefiniteArray => OwnedEvent::IndefiniteArray,
+	    Event::IndefiniteMap => OwnedEvent::IndefiniteMap,
+	    Event::Tag(val) => OwnedEvent::Tag(val),
+	    Event::Simple(val) => OwnedEvent::Simple(val),
+	    Event::HalfFloat(bytes) => OwnedEvent::HalfFloat(*bytes),
+	    Event::SingleFloat(bytes) => OwnedEvent::SingleFloat(*bytes),
+	    Event::DoubleFloat(bytes) => OwnedEvent::DoubleFloat(*bytes),
+	    Event::Float(val) => OwnedEvent::Float(val),
+	    Event::Break => OwnedEvent::Break,
+	    Event::End => OwnedEvent::End
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_owned_event() {
+	use crate::encode::Encoder;
+
+	let events = [
+	    Event::Map(1),
+	    Event::TextString(b"a"),
+	    Event::IndefiniteByteString,
+	    Event::ByteString(&[0x01, 0x02]),
+	    Event::Break,
+	    Event::Tag(1),
+	    Event::HalfFloat(&[0x3C, 0x00]),
+	    Event::Simple(99)
+	];
+
+	let mut borrowed = Vec::new();
+	let mut enc = Encoder::new(&mut borrowed);
+	for event in &events {
+	    let _ = enc.encode_event(event);
+	}
+
+	let mut owned = Vec::new();
+	let mut enc = Encoder::new(&mut owned);
+	for event in events {
+	    let event = OwnedEvent::from(event);
+	    let _ = enc.encode_event(&event.as_event());
+	}
+
+	assert_eq!(owned, borrowed);
+	assert_eq!(OwnedEvent::from(Event::TextString(b"a")), OwnedEvent::TextString(b"a".to_vec()));
+	assert_eq!(OwnedEvent::DoubleFloat([0x40; 8]).as_event(), Event::DoubleFloat(&[0x40; 8]));
+    }
+
+    #[test]
+    fn test_cmp_canonical() {
+	assert_eq!(Event::UnsignedInteger(10).cmp_canonical(&Event::UnsignedInteger(100)), Ordering::Less);
+	assert_eq!(Event::UnsignedInteger(10).cmp_canonical(&Event::Array(2)), Ordering::Less);
+	assert_eq!(Event::UnsignedInteger(100).cmp_canonical(&Event::Array(2)), Ordering::Greater);
+	assert_eq!(Event::UnsignedInteger(100).cmp_canonical(&Event::UnsignedInteger(100)), Ordering::Equal);
+	assert_eq!(Event::NegativeInteger(0).cmp_canonical(&Event::UnsignedInteger(23)), Ordering::Greater);
+	assert_eq!(Event::TextString(b"z").cmp_canonical(&Event::TextString(b"aa")), Ordering::Less);
+	assert_eq!(Event::TextString(b"ab").cmp_canonical(&Event::TextString(b"aa")), Ordering::Greater);
+	assert_eq!(Event::ByteString(b"a").cmp_canonical(&Event::UnsignedInteger(24)), Ordering::Greater);
+	assert_eq!(Event::HalfFloat(&[0x3C, 0x00]).cmp_canonical(&Event::UnsignedInteger(256)), Ordering::Greater);
+	assert_eq!(Event::Simple(22).cmp_canonical(&Event::IndefiniteArray), Ordering::Greater);
+	assert_eq!(Event::End.cmp_canonical(&Event::DoubleFloat(&[0xFF; 8])), Ordering::Greater);
+	assert_eq!(Event::End.cmp_canonical(&Event::Simple(24)), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_ord() {
+	let mut events = [
+	    Event::End,
+	    Event::TextString(b"aa"),
+	    Event::UnsignedInteger(24),
+	    Event::Simple(25),
+	    Event::Map(0),
+	    Event::HalfFloat(&[0x3C, 0x00]),
+	    Event::NegativeInteger(0),
+	    Event::Break,
+	    Event::UnsignedInteger(10),
+	    Event::Simple(24),
+	    Event::TextString(b"z"),
+	    Event::Tag(1),
+	    Event::ByteString(b"a")
+	];
+	events.sort();
+
+	assert_eq!(events, [
+	    Event::UnsignedInteger(10),
+	    Event::NegativeInteger(0),
+	    Event::Map(0),
+	    Event::Tag(1),
+	    Event::Break,
+	    Event::UnsignedInteger(24),
+	    Event::ByteString(b"a"),
+	    Event::TextString(b"z"),
+	    Event::TextString(b"aa"),
+	    Event::HalfFloat(&[0x3C, 0x00]),
+	    Event::Simple(24),
+	    Event::Simple(25),
+	    Event::End
+	]);
+	assert!(Event::Simple(24) < Event::End);
+	assert!(Event::HalfFloat(&[0x3C, 0x00]) < Event::Float(1.0));
+	assert!(Event::Float(1.0) < Event::SingleFloat(&[0x3F, 0x80, 0x00, 0x00]));
+	assert_ne!(Event::Float(f64::NAN).cmp(&Event::Float(-f64::NAN)), Ordering::Equal);
+	assert_eq!(Event::UnsignedInteger(1).cmp(&Event::UnsignedInteger(1)), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_encoded_len() {
+	assert_eq!(Event::UnsignedInteger(0x17).encoded_len(), Some(1));
+	assert_eq!(Event::UnsignedInteger(0x087B).encoded_len(), Some(3));
+	assert_eq!(Event::NegativeInteger(0x1_0000_0000).encoded_len(), Some(9));
+	assert_eq!(Event::ByteString(&[0, 0]).encoded_len(), Some(3));
+	assert_eq!(Event::TextString(&[0x61; 24]).encoded_len(), Some(26));
+	assert_eq!(Event::Map(0x10000).encoded_len(), Some(5));
+	assert_eq!(Event::IndefiniteArray.encoded_len(), Some(1));
+	assert_eq!(Event::Simple(20).encoded_len(), Some(1));
+	assert_eq!(Event::Simple(32).encoded_len(), Some(2));
+	assert_eq!(Event::Simple(24).encoded_len(), None);
+	assert_eq!(Event::SingleFloat(&[0; 4]).encoded_len(), Some(5));
+	assert_eq!(Event::End.encoded_len(), None);
+    }
+
+    #[test]
+    fn test_head_len() {
+	assert_eq!(Event::Array(1000).head_len(), 3);
+	assert_eq!(Event::UnsignedInteger(5).head_len(), 1);
+	assert_eq!(Event::NegativeInteger(u64::MAX).head_len(), 9);
+	assert_eq!(Event::ByteString(&[0; 24]).head_len(), 2);
+	assert_eq!(Event::IndefiniteMap.head_len(), 1);
+	assert_eq!(Event::Simple(20).head_len(), 1);
+	assert_eq!(Event::Simple(32).head_len(), 2);
+	assert_eq!(Event::SingleFloat(&[0; 4]).head_len(), 5);
+	assert_eq!(Event::Float(1.5).head_len(), 3);
+	assert_eq!(Event::Break.head_len(), 1);
+	assert_eq!(Event::End.head_len(), 0);
+    }
+
+    #[test]
+    fn test_content_len() {
+	assert_eq!(Event::ByteString(&[1, 2, 3, 4, 5]).content_len(), Some(5));
+	assert_eq!(Event::TextString(b"").content_len(), Some(0));
+	assert_eq!(Event::UnsignedInteger(5).content_len(), None);
+	assert_eq!(Event::Array(5).content_len(), None);
+	assert_eq!(Event::IndefiniteByteString.content_len(), None);
+    }
+
+    #[test]
+    fn test_major_type() {
+	assert_eq!(Event::UnsignedInteger(1).major_type(), Some(0));
+	assert_eq!(Event::NegativeInteger(1).major_type(), Some(1));
+	assert_eq!(Event::ByteString(b"a").major_type(), Some(2));
+	assert_eq!(Event::IndefiniteTextString.major_type(), Some(3));
+	assert_eq!(Event::Array(2).major_type(), Some(4));
+	assert_eq!(Event::IndefiniteMap.major_type(), Some(5));
+	assert_eq!(Event::Tag(1).major_type(), Some(6));
+	assert_eq!(Event::Simple(20).major_type(), Some(7));
+	assert_eq!(Event::DoubleFloat(&[0; 8]).major_type(), Some(7));
+	assert_eq!(Event::Break.major_type(), None);
+	assert_eq!(Event::End.major_type(), None);
+    }
+
+    #[test]
+    fn test_float_bytes() {
+	assert_eq!(Event::HalfFloat(&[0x3C, 0x00]).float_bytes(), Some(&[0x3C, 0x00][..]));
+	assert_eq!(Event::SingleFloat(&[0x3F, 0xC0, 0x00, 0x00]).float_bytes(), Some(&[0x3F, 0xC0, 0x00, 0x00][..]));
+	assert_eq!(Event::DoubleFloat(&[0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A]).float_bytes(), Some(&[0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A][..]));
+	assert_eq!(Event::Float(1.0).float_bytes(), None);
+	assert_eq!(Event::UnsignedInteger(1).float_bytes(), None);
+	assert_eq!(Event::ByteString(&[0x3C, 0x00]).float_bytes(), None);
+    }
+
+    #[test]
+    fn test_value_eq() {
+	assert!(Event::HalfFloat(&[0x3C, 0x00]).value_eq(&Event::DoubleFloat(&1.0_f64.to_be_bytes())));
+	assert!(Event::SingleFloat(&1.5_f32.to_be_bytes()).value_eq(&Event::Float(1.5)));
+	assert!(Event::HalfFloat(&[0x80, 0x00]).value_eq(&Event::Float(0.0)));
+	assert!(!Event::HalfFloat(&[0x3C, 0x00]).value_eq(&Event::DoubleFloat(&1.1_f64.to_be_bytes())));
+	assert!(!Event::HalfFloat(&[0x7E, 0x00]).value_eq(&Event::HalfFloat(&[0x7E, 0x00])));
+	assert!(!Event::Float(1.0).value_eq(&Event::UnsignedInteger(1)));
+
+	assert!(Event::UnsignedInteger(1).value_eq(&Event::UnsignedInteger(1)));
+	assert!(!Event::UnsignedInteger(0).value_eq(&Event::NegativeInteger(0)));
+	assert!(Event::TextString(b"a").value_eq(&Event::TextString(b"a")));
+    }
+
+}
@@ -0,0 +1,51 @@
+use alloc::vec::Vec;
+use crate::decode::decode_all;
+use crate::encode::Encoder;
+
+/// バイト列をデコードして再エンコードし、その結果が同じイベントの列にデコードされることを検査する。
+///
+/// 整形式でないバイト列の場合は何もしない。往復で結果が異なる場合はパニックする。
+pub fn fuzz_roundtrip(data: &[u8]) {
+    let events = match decode_all(data) {
+	Ok(events) => events,
+	Err(_) => return
+    };
+
+    let mut buf = Vec::with_capacity(data.len());
+    let mut enc = Encoder::new(&mut buf);
+    for event in &events {
+	if let Err(err) = enc.encode_event(event) {
+	    panic!("failed to re-encode {:?}: {:?}", event, err);
+	}
+    }
+
+    match decode_all(&buf) {
+	Ok(decoded) => assert_eq!(decoded, events),
+	Err(err) => panic!("failed to decode re-encoded data: {:?}", err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_roundtrip() {
+	let documents: &[&[u8]] = &[
+	    &[0xA1, 0x18, 0x2A, 0x65, 0x68, 0x65, 0x6C, 0x6C, 0x6F],
+	    &[0x9F, 0x01, 0x82, 0x02, 0x03, 0xFF],
+	    &[0x5F, 0x41, 0x01, 0x42, 0x02, 0x03, 0xFF],
+	    &[0xC2, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+	    &[0xBF, 0x61, 0x61, 0xF9, 0x3C, 0x00, 0xFF],
+	    &[0x19, 0x00, 0x01, 0xF8, 0x20, 0xFB, 0x40, 0x09, 0x21, 0xFB, 0x54, 0x44, 0x2D, 0x18],
+	    &[0x82, 0x01],
+	    &[0x1C],
+	    &[]
+	];
+
+	for data in documents {
+	    fuzz_roundtrip(data);
+	}
+    }
+
+}
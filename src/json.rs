@@ -0,0 +1,544 @@
+
+use core::fmt::Write;
+use core::result;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::event::*;
+use crate::decode;
+use crate::decode::{Decoder, MAX_NESTING_DEPTH};
+use crate::encode::Encoder;
+use crate::value::Value;
+use crate::diag;
+use crate::diag::write_text;
+use crate::misc::*;
+
+/// JSONとの変換時に発生するエラー。
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Error {
+    /// CBORデータが不正である場合。
+    Decode(decode::Error),
+
+    /// undefined、その他の単純値、NaN、無限大など、JSONで表せない値が現れた場合。
+    UnsupportedValue,
+
+    /// 最初のデータ項目の後にデータが続いている場合。
+    TrailingData,
+
+    /// JSON文字列が不正である場合。パラメーターは問題の見つかったバイト位置。
+    InvalidJson(usize),
+
+    /// JSONの配列・オブジェクトの入れ子が深すぎる場合。
+    NestingTooDeep
+}
+
+impl Eq for Error {}
+
+impl From<decode::Error> for Error {
+    fn from(err: decode::Error) -> Error {
+	Error::Decode(err)
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+fn write_base64url(output: &mut String, bytes: &[u8]) {
+    output.push('"');
+    diag::write_base64url(output, bytes);
+    output.push('"');
+}
+
+fn write_float(output: &mut String, val: f64, single: bool) -> Result<()> {
+    if !val.is_finite() {
+	return Err(Error::UnsupportedValue);
+    }
+
+    if single {
+	let _ = write!(output, "{:?}", val as f32);
+    } else {
+	let _ = write!(output, "{:?}", val);
+    }
+
+    Ok(())
+}
+
+struct Converter<'a> {
+    decoder: Decoder<'a>,
+    output: String
+}
+
+impl<'a> Converter<'a> {
+
+    fn write_item(&mut self, depth: usize) -> Result<()> {
+	if depth >= MAX_NESTING_DEPTH {
+	    return Err(Error::Decode(decode::Error::NestingTooDeep));
+	}
+
+	match self.decoder.decode_event()? {
+	    Event::UnsignedInteger(val) => {
+		let _ = write!(self.output, "{}", val);
+	    },
+	    Event::NegativeInteger(val) => {
+		let _ = write!(self.output, "{}", -1 - (val as i128));
+	    },
+	    Event::ByteString(content) => write_base64url(&mut self.output, content),
+	    Event::TextString(content) => {
+		let text = core::str::from_utf8(content).map_err(|_| decode::Error::InvalidUtf8)?;
+		write_text(&mut self.output, text);
+	    },
+	    Event::IndefiniteByteString => {
+		let content = self.decoder.read_indefinite_bytes()?;
+		write_base64url(&mut self.output, &content);
+	    },
+	    Event::IndefiniteTextString => {
+		let text = self.decoder.read_indefinite_text()?;
+		write_text(&mut self.output, &text);
+	    },
+	    Event::Array(len) => {
+		self.output.push('[');
+		for i in 0..len {
+		    if i > 0 {
+			self.output.push(',');
+		    }
+		    self.write_item(depth + 1)?;
+		}
+		self.output.push(']');
+	    },
+	    Event::Map(len) => {
+		self.output.push('{');
+		for i in 0..len {
+		    if i > 0 {
+			self.output.push(',');
+		    }
+		    self.write_entry(depth)?;
+		}
+		self.output.push('}');
+	    },
+	    Event::IndefiniteArray => {
+		self.output.push('[');
+		let mut first = true;
+		while !self.skip_break()? {
+		    if !first {
+			self.output.push(',');
+		    }
+		    first = false;
+		    self.write_item(depth + 1)?;
+		}
+		self.output.push(']');
+	    },
+	    Event::IndefiniteMap => {
+		self.output.push('{');
+		let mut first = true;
+		while !self.skip_break()? {
+		    if !first {
+			self.output.push(',');
+		    }
+		    first = false;
+		    self.write_entry(depth)?;
+		}
+		self.output.push('}');
+	    },
+	    Event::Tag(_) => self.write_item(depth + 1)?,
+	    Event::Simple(SIMPLE_VALUE_FALSE) => self.output.push_str("false"),
+	    Event::Simple(SIMPLE_VALUE_TRUE) => self.output.push_str("true"),
+	    Event::Simple(SIMPLE_VALUE_NULL) => self.output.push_str("null"),
+	    Event::Simple(_) => return Err(Error::UnsupportedValue),
+	    Event::HalfFloat(bytes) => write_float(&mut self.output, half_to_f64(bytes), true)?,
+	    Event::SingleFloat(bytes) => write_float(&mut self.output, f32::from_be_bytes(*bytes) as f64, true)?,
+	    Event::DoubleFloat(bytes) => write_float(&mut self.output, f64::from_be_bytes(*bytes), false)?,
+	    Event::Float(val) => write_float(&mut self.output, val, false)?,
+	    Event::Break => return Err(Error::Decode(decode::Error::UnexpectedBreak)),
+	    Event::End => return Err(Error::Decode(decode::Error::UnexpectedEnd))
+	}
+
+	Ok(())
+    }
+
+    fn write_entry(&mut self, depth: usize) -> Result<()> {
+	let start = self.output.len();
+	self.write_item(depth + 1)?;
+
+	if !self.output[start..].starts_with('"') {
+	    let key = self.output.split_off(start);
+	    write_text(&mut self.output, &key);
+	}
+
+	self.output.push(':');
+	self.write_item(depth + 1)
+    }
+
+    fn skip_break(&mut self) -> Result<bool> {
+	match self.decoder.peek_event()? {
+	    Event::Break => {
+		self.decoder.decode_event()?;
+		Ok(true)
+	    },
+	    Event::End => Err(Error::Decode(decode::Error::UnexpectedEnd)),
+	    _ => Ok(false)
+	}
+    }
+
+}
+
+/// 1つのデータ項目からなるCBORデータをJSON文字列に変換する。
+///
+/// 整数・浮動小数点数・真偽値・nullはそのままJSONの値に、文字列はJSONの文字列に、バイト列はパディングなしのbase64urlで符号化した文字列に変換する。
+/// マップのキーが文字列でない場合は、キーを変換したJSONの文字列表現をキーとする。
+/// タグはタグ番号を捨てて内容のデータ項目だけを変換する。
+/// undefined、その他の単純値、NaN、無限大はJSONで表せないため`UnsupportedValue`エラーとする。
+pub fn to_json(data: &[u8]) -> Result<String> {
+    let mut converter = Converter {
+	decoder: Decoder::new(data),
+	output: String::new()
+    };
+
+    converter.write_item(0)?;
+
+    if converter.decoder.remaining().is_empty() {
+	Ok(converter.output)
+    } else {
+	Err(Error::TrailingData)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+
+    fn error<T>(&self) -> Result<T> {
+	Err(Error::InvalidJson(self.pos))
+    }
+
+    fn skip_whitespace(&mut self) {
+	while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.get(self.pos) {
+	    self.pos += 1;
+	}
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+	self.skip_whitespace();
+	self.input.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, b: u8) -> Result<()> {
+	if self.peek() == Some(b) {
+	    self.pos += 1;
+	    Ok(())
+	} else {
+	    self.error()
+	}
+    }
+
+    fn expect_literal(&mut self, literal: &[u8]) -> Result<()> {
+	if self.input[self.pos..].starts_with(literal) {
+	    self.pos += literal.len();
+	    Ok(())
+	} else {
+	    self.error()
+	}
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<Value> {
+	if depth >= MAX_NESTING_DEPTH {
+	    return Err(Error::NestingTooDeep);
+	}
+
+	match self.peek() {
+	    Some(b'{') => {
+		self.pos += 1;
+		let mut entries = Vec::new();
+		if self.peek() == Some(b'}') {
+		    self.pos += 1;
+		    return Ok(Value::Map(entries));
+		}
+		loop {
+		    if self.peek() != Some(b'"') {
+			return self.error();
+		    }
+		    let key = Value::Text(self.parse_string()?);
+		    self.expect(b':')?;
+		    entries.push((key, self.parse_value(depth + 1)?));
+		    match self.peek() {
+			Some(b',') => self.pos += 1,
+			Some(b'}') => {
+			    self.pos += 1;
+			    return Ok(Value::Map(entries));
+			},
+			_ => return self.error()
+		    }
+		}
+	    },
+	    Some(b'[') => {
+		self.pos += 1;
+		let mut items = Vec::new();
+		if self.peek() == Some(b']') {
+		    self.pos += 1;
+		    return Ok(Value::Array(items));
+		}
+		loop {
+		    items.push(self.parse_value(depth + 1)?);
+		    match self.peek() {
+			Some(b',') => self.pos += 1,
+			Some(b']') => {
+			    self.pos += 1;
+			    return Ok(Value::Array(items));
+			},
+			_ => return self.error()
+		    }
+		}
+	    },
+	    Some(b'"') => Ok(Value::Text(self.parse_string()?)),
+	    Some(b't') => self.expect_literal(b"true").map(|_| Value::Bool(true)),
+	    Some(b'f') => self.expect_literal(b"false").map(|_| Value::Bool(false)),
+	    Some(b'n') => self.expect_literal(b"null").map(|_| Value::Null),
+	    Some(b'-' | b'0'..=b'9') => self.parse_number(),
+	    _ => self.error()
+	}
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32> {
+	let digits = match self.input.get(self.pos..self.pos + 4) {
+	    Some(digits) => digits,
+	    None => return self.error()
+	};
+
+	let mut val = 0;
+	for &b in digits {
+	    match (b as char).to_digit(16) {
+		Some(digit) => val = (val << 4) | digit,
+		None => return self.error()
+	    }
+	}
+	self.pos += 4;
+
+	Ok(val)
+    }
+
+    fn parse_escape(&mut self) -> Result<char> {
+	let c = match self.input.get(self.pos) {
+	    Some(b'"') => '"',
+	    Some(b'\\') => '\\',
+	    Some(b'/') => '/',
+	    Some(b'b') => '\u{08}',
+	    Some(b'f') => '\u{0C}',
+	    Some(b'n') => '\n',
+	    Some(b'r') => '\r',
+	    Some(b't') => '\t',
+	    Some(b'u') => {
+		self.pos += 1;
+		let start = self.pos;
+		let mut code = self.parse_hex4()?;
+		if (0xD800..0xDC00).contains(&code) {
+		    self.expect_literal(b"\\u")?;
+		    let low = self.parse_hex4()?;
+		    if !(0xDC00..0xE000).contains(&low) {
+			self.pos = start;
+			return self.error();
+		    }
+		    code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+		}
+		return match char::from_u32(code) {
+		    Some(c) => Ok(c),
+		    None => {
+			self.pos = start;
+			self.error()
+		    }
+		};
+	    },
+	    _ => return self.error()
+	};
+	self.pos += 1;
+
+	Ok(c)
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+	self.pos += 1;
+	let mut text = String::new();
+
+	loop {
+	    let start = self.pos;
+	    while let Some(&b) = self.input.get(self.pos) {
+		if b == b'"' || b == b'\\' || b < 0x20 {
+		    break;
+		}
+		self.pos += 1;
+	    }
+	    // 入力は&strなので、ASCII文字の位置で区切った部分は正しいUTF-8である。
+	    text.push_str(core::str::from_utf8(&self.input[start..self.pos]).map_err(|_| Error::InvalidJson(start))?);
+
+	    match self.input.get(self.pos) {
+		Some(b'"') => {
+		    self.pos += 1;
+		    return Ok(text);
+		},
+		Some(b'\\') => {
+		    self.pos += 1;
+		    let c = self.parse_escape()?;
+		    text.push(c);
+		},
+		_ => return self.error()
+	    }
+	}
+    }
+
+    fn skip_digits(&mut self) -> usize {
+	let start = self.pos;
+	while let Some(b'0'..=b'9') = self.input.get(self.pos) {
+	    self.pos += 1;
+	}
+	self.pos - start
+    }
+
+    fn parse_number(&mut self) -> Result<Value> {
+	let start = self.pos;
+	let mut is_float = false;
+
+	if self.input[self.pos] == b'-' {
+	    self.pos += 1;
+	}
+	match self.input.get(self.pos) {
+	    Some(b'0') => self.pos += 1,
+	    Some(b'1'..=b'9') => {
+		self.skip_digits();
+	    },
+	    _ => return self.error()
+	}
+	if self.input.get(self.pos) == Some(&b'.') {
+	    is_float = true;
+	    self.pos += 1;
+	    if self.skip_digits() == 0 {
+		return self.error();
+	    }
+	}
+	if let Some(b'e' | b'E') = self.input.get(self.pos) {
+	    is_float = true;
+	    self.pos += 1;
+	    if let Some(b'+' | b'-') = self.input.get(self.pos) {
+		self.pos += 1;
+	    }
+	    if self.skip_digits() == 0 {
+		return self.error();
+	    }
+	}
+
+	// 数字と記号だけからなるので正しいUTF-8である。
+	let literal = core::str::from_utf8(&self.input[start..self.pos]).map_err(|_| Error::InvalidJson(start))?;
+
+	if !is_float {
+	    if let Ok(val) = literal.parse::<i128>() {
+		if (-(1_i128 << 64)..(1_i128 << 64)).contains(&val) {
+		    return Ok(Value::Integer(val));
+		}
+	    }
+	}
+
+	match literal.parse::<f64>() {
+	    Ok(val) => Ok(Value::Float(val)),
+	    Err(_) => Err(Error::InvalidJson(start))
+	}
+    }
+
+}
+
+/// JSON文字列をCBORデータに変換する。
+///
+/// 小数点も指数も含まない数値は、CBORの整数で表せる範囲(-2^64〜2^64-1)であれば整数に、範囲外であれば浮動小数点数に変換する。
+/// 小数点または指数を含む数値は、`1.0`のように整数値であっても浮動小数点数とする。
+/// 整数・浮動小数点数は値を正確に表せる最も短い形式でエンコードする。
+pub fn from_json(text: &str) -> Result<Vec<u8>> {
+    let mut parser = Parser {
+	input: text.as_bytes(),
+	pos: 0
+    };
+
+    let value = parser.parse_value(0)?;
+    if parser.peek().is_some() {
+	return Err(Error::TrailingData);
+    }
+
+    let mut buf = Vec::new();
+    // Vec<u8>への書き込みは失敗しない。
+    let _ = value.encode(&mut Encoder::new(&mut buf));
+
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_to_json() {
+	assert_eq!(to_json(&[
+	    0xA4,
+	    0x61, 0x61, 0x83, 0x01, 0x38, 0x63, 0xF9, 0x3E, 0x00,
+	    0x61, 0x62, 0xA2, 0x01, 0xF5, 0x82, 0x01, 0x02, 0xF6,
+	    0x61, 0x63, 0x43, 0xFB, 0xFF, 0x00,
+	    0x61, 0x64, 0xC1, 0x7F, 0x61, 0x22, 0x61, 0x0A, 0xFF
+	]), Ok(String::from("{\"a\":[1,-100,1.5],\"b\":{\"1\":true,\"[1,2]\":null},\"c\":\"-_8A\",\"d\":\"\\\"\\n\"}")));
+
+	assert_eq!(to_json(&[0xFA, 0x3D, 0xCC, 0xCC, 0xCD]), Ok(String::from("0.1")));
+	assert_eq!(to_json(&[0x9F, 0x5F, 0x41, 0x01, 0xFF, 0xBF, 0xFF, 0xFF]), Ok(String::from("[\"AQ\",{}]")));
+	assert_eq!(to_json(&[0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]), Ok(String::from("-18446744073709551616")));
+    }
+
+    #[test]
+    fn test_base64url() {
+	let mut output = String::new();
+	write_base64url(&mut output, b"");
+	write_base64url(&mut output, b"f");
+	write_base64url(&mut output, b"fo");
+	write_base64url(&mut output, b"foo");
+	write_base64url(&mut output, b"foob");
+	assert_eq!(output, "\"\"\"Zg\"\"Zm8\"\"Zm9v\"\"Zm9vYg\"");
+    }
+
+    #[test]
+    fn test_from_json() {
+	assert_eq!(from_json(" { \"a\" : [1, -100, 1.5, 1.0, 1e2], \"b\": {\"c\": true, \"d\": null, \"e\": false} } "), Ok(vec![
+	    0xA2,
+	    0x61, 0x61, 0x85, 0x01, 0x38, 0x63, 0xF9, 0x3E, 0x00, 0xF9, 0x3C, 0x00, 0xF9, 0x56, 0x40,
+	    0x61, 0x62, 0xA3, 0x61, 0x63, 0xF5, 0x61, 0x64, 0xF6, 0x61, 0x65, 0xF4
+	]));
+
+	assert_eq!(from_json("\"\\u00e9\\ud83d\\ude00\\n\""), Ok(vec![0x67, 0xC3, 0xA9, 0xF0, 0x9F, 0x98, 0x80, 0x0A]));
+	assert_eq!(from_json("18446744073709551615"), Ok(vec![0x1B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]));
+	assert_eq!(from_json("-18446744073709551616"), Ok(vec![0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]));
+	assert_eq!(from_json("18446744073709551616"), Ok(vec![0xFA, 0x5F, 0x80, 0x00, 0x00]));
+	assert_eq!(from_json("0.1"), Ok(vec![0xFB, 0x3F, 0xB9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A]));
+    }
+
+    #[test]
+    fn test_from_json_err() {
+	assert_eq!(from_json(""), Err(Error::InvalidJson(0)));
+	assert_eq!(from_json("[1,]"), Err(Error::InvalidJson(3)));
+	assert_eq!(from_json("{1: 2}"), Err(Error::InvalidJson(1)));
+	assert_eq!(from_json("01"), Err(Error::TrailingData));
+	assert_eq!(from_json("1."), Err(Error::InvalidJson(2)));
+	assert_eq!(from_json("\"\\ud800\""), Err(Error::InvalidJson(7)));
+	assert_eq!(from_json("\"a\nb\""), Err(Error::InvalidJson(2)));
+	assert_eq!(from_json("nul"), Err(Error::InvalidJson(0)));
+	assert_eq!(from_json(&"[".repeat(1000)), Err(Error::NestingTooDeep));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+	let json = "{\"name\":\"cbor\",\"tags\":[\"a\",\"b\"],\"size\":-3,\"ratio\":0.25,\"nested\":{\"ok\":true,\"none\":null}}";
+	assert_eq!(to_json(&from_json(json).unwrap()), Ok(String::from(json)));
+    }
+
+    #[test]
+    fn test_to_json_err() {
+	assert_eq!(to_json(&[0xF7]), Err(Error::UnsupportedValue));
+	assert_eq!(to_json(&[0xF9, 0x7E, 0x00]), Err(Error::UnsupportedValue));
+	assert_eq!(to_json(&[0x01, 0x02]), Err(Error::TrailingData));
+	assert_eq!(to_json(&[]), Err(Error::Decode(decode::Error::UnexpectedEnd)));
+	assert_eq!(to_json(&[0x82, 0x01]), Err(Error::Decode(decode::Error::UnexpectedEnd)));
+    }
+
+}
@@ -1,3 +1,8 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 
 /// イベントを定義するモジュール。
 pub mod event;
@@ -9,3 +14,66 @@ pub mod decode;
 pub mod encode;
 
 pub mod misc;
+
+/// データ項目の先頭部分を直接扱う低水準のモジュール。
+pub mod raw;
+
+/// イベントを書き換えながら再エンコードするモジュール。
+pub mod transform;
+
+/// よく使われるタグ番号を定義するモジュール。
+pub mod tags;
+
+/// `io::Read`からのデコーダーを定義するモジュール。
+#[cfg(feature = "std")]
+pub mod read;
+
+/// `AsyncRead`からの非同期デコーダーを定義するモジュール。
+#[cfg(feature = "async")]
+pub mod async_read;
+
+/// エポック日時タグと`SystemTime`の変換を定義するモジュール。
+#[cfg(feature = "time")]
+pub mod time;
+
+/// 値の木構造を定義するモジュール。
+#[cfg(feature = "alloc")]
+pub mod value;
+
+/// 値の木構造をまとめて格納するアリーナを定義するモジュール。
+#[cfg(feature = "alloc")]
+pub mod arena;
+
+/// 診断記法を扱うモジュール。
+#[cfg(feature = "alloc")]
+pub mod diag;
+
+/// JSONとの変換を定義するモジュール。
+#[cfg(feature = "alloc")]
+pub mod json;
+
+/// ファジング用のエントリーポイントを定義するモジュール。
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+
+/// serdeとの連携を定義するモジュール。
+#[cfg(feature = "serde")]
+pub mod serde;
+
+#[cfg(all(test, not(feature = "std")))]
+mod tests {
+    use crate::event::Event;
+    use crate::decode::*;
+
+    #[test]
+    fn test_no_std_decode() {
+	let mut dec = Decoder::new(&[0x82, 0x01, 0xF9, 0x3C, 0x00]);
+
+	assert_eq!(dec.decode_event(), Ok(Event::Array(2)));
+	assert_eq!(dec.decode_event(), Ok(Event::UnsignedInteger(1)));
+	assert_eq!(dec.decode_event(), Ok(Event::HalfFloat(&[0x3C, 0x00])));
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+	assert_eq!(validate(&[0x82, 0x01, 0x02]), Ok(1));
+    }
+
+}
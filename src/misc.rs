@@ -1,4 +1,8 @@
 
+use core::cmp::Ordering;
+use crate::event::Event;
+use crate::tags::{POSITIVE_BIGNUM, NEGATIVE_BIGNUM, EPOCH_DATETIME};
+
 /// falseを表す単純値。
 pub const SIMPLE_VALUE_FALSE: u8 = 20;
 
@@ -10,3 +14,261 @@ pub const SIMPLE_VALUE_NULL: u8 = 22;
 
 /// undefinedを表す単純値。
 pub const SIMPLE_VALUE_UNDEFINED: u8 = 23;
+
+/// 24未満の単純値をエンコードした1バイトを返す。定数の初期化に使える。
+///
+/// 24以上の単純値は1バイトでは表せないためパニックする。定数の文脈ではコンパイルエラーになる。
+pub const fn simple_initial_byte(val: u8) -> u8 {
+    assert!(val < 24, "simple value must be less than 24");
+    0xE0 | val
+}
+
+/// 空の配列をエンコードしたバイト列。
+pub const EMPTY_ARRAY: [u8; 1] = [0x80];
+
+/// 空の連想配列をエンコードしたバイト列。
+pub const EMPTY_MAP: [u8; 1] = [0xA0];
+
+/// nullをエンコードしたバイト列。
+pub const NULL: [u8; 1] = [simple_initial_byte(SIMPLE_VALUE_NULL)];
+
+/// 単純値。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SimpleValue {
+    /// false。
+    False,
+
+    /// true。
+    True,
+
+    /// null。
+    Null,
+
+    /// undefined。
+    Undefined,
+
+    /// その他の単純値。
+    Other(u8)
+}
+
+impl SimpleValue {
+
+    /// 単純値の番号から作成する。
+    pub fn from_u8(val: u8) -> SimpleValue {
+	match val {
+	    SIMPLE_VALUE_FALSE => SimpleValue::False,
+	    SIMPLE_VALUE_TRUE => SimpleValue::True,
+	    SIMPLE_VALUE_NULL => SimpleValue::Null,
+	    SIMPLE_VALUE_UNDEFINED => SimpleValue::Undefined,
+	    val => SimpleValue::Other(val)
+	}
+    }
+
+    /// 単純値の番号を返す。
+    pub fn to_u8(self) -> u8 {
+	match self {
+	    SimpleValue::False => SIMPLE_VALUE_FALSE,
+	    SimpleValue::True => SIMPLE_VALUE_TRUE,
+	    SimpleValue::Null => SIMPLE_VALUE_NULL,
+	    SimpleValue::Undefined => SIMPLE_VALUE_UNDEFINED,
+	    SimpleValue::Other(val) => val
+	}
+    }
+
+}
+
+/// エンコード済みのマップのキーをRFC 8949 4.2.1節の順序で比較する。短いものが先で、同じ長さなら辞書順とする。
+pub fn key_order(a: &[u8], b: &[u8]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// 半精度浮動小数点数のバイト列を倍精度浮動小数点数に変換する。
+pub fn half_to_f64(bytes: &[u8; 2]) -> f64 {
+    let half = u16::from_be_bytes(*bytes);
+    let exp = ((half >> 10) & 0x1F) as u64;
+    let mant = (half & 0x3FF) as u64;
+    let val = match exp {
+	0 => mant as f64 / 16_777_216.0,
+	31 => f64::from_bits(0x7FF0_0000_0000_0000 | (mant << 42)),
+	_ => f64::from_bits(((exp + 1008) << 52) | (mant << 42))
+    };
+
+    if half & 0x8000 != 0 { -val } else { val }
+}
+
+/// 倍精度浮動小数点数を値を変えずに半精度浮動小数点数で表せる場合、そのバイト列を返す。
+pub fn f64_to_half(val: f64) -> Option<[u8; 2]> {
+    let bits = val.to_bits();
+    let sign = ((bits >> 48) & 0x8000) as u16;
+    let exp = ((bits >> 52) & 0x7FF) as i32;
+    let mant = bits & 0x000F_FFFF_FFFF_FFFF;
+
+    let half = if exp == 0x7FF {
+	if mant & 0x3FF_FFFF_FFFF != 0 {
+	    return None;
+	}
+	sign | 0x7C00 | (mant >> 42) as u16
+    } else if exp == 0 && mant == 0 {
+	sign
+    } else {
+	let e = exp - 1023;
+	if (-14..=15).contains(&e) {
+	    if mant & 0x3FF_FFFF_FFFF != 0 {
+		return None;
+	    }
+	    sign | (((e + 15) as u16) << 10) | (mant >> 42) as u16
+	} else if (-24..-14).contains(&e) {
+	    let shift = 28 - e;
+	    let full = mant | 0x0010_0000_0000_0000;
+	    if full & ((1 << shift) - 1) != 0 {
+		return None;
+	    }
+	    sign | (full >> shift) as u16
+	} else {
+	    return None;
+	}
+    };
+
+    Some(half.to_be_bytes())
+}
+
+/// 多倍長整数タグ (2または3) とその内容のバイト列を整数に変換する。`i128`で表せない場合は`None`を返す。
+pub fn bignum_to_i128(tag: u64, bytes: &[u8]) -> Option<i128> {
+    let mut magnitude = 0_u128;
+
+    for &b in bytes {
+	if magnitude >> 120 != 0 {
+	    return None;
+	}
+	magnitude = (magnitude << 8) | (b as u128);
+    }
+
+    let magnitude = i128::try_from(magnitude).ok()?;
+
+    match tag {
+	POSITIVE_BIGNUM => Some(magnitude),
+	NEGATIVE_BIGNUM => Some(-1 - magnitude),
+	_ => None
+    }
+}
+
+/// エポック日時タグ (1) とその内容のイベントからエポックからの秒数を求める。
+pub fn epoch_from_events(tag: u64, value: &Event) -> Option<f64> {
+    if tag != EPOCH_DATETIME {
+	return None;
+    }
+
+    match value {
+	Event::UnsignedInteger(val) => Some(*val as f64),
+	Event::NegativeInteger(val) => Some(-1.0 - *val as f64),
+	Event::HalfFloat(bytes) => Some(half_to_f64(bytes)),
+	Event::SingleFloat(bytes) => Some(f32::from_be_bytes(**bytes) as f64),
+	Event::DoubleFloat(bytes) => Some(f64::from_be_bytes(**bytes)),
+	Event::Float(val) => Some(*val),
+	_ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_order() {
+	assert_eq!(key_order(&[0x0A], &[0x18, 0x64]), Ordering::Less);
+	assert_eq!(key_order(&[0x61, 0x7A], &[0x62, 0x61, 0x61]), Ordering::Less);
+	assert_eq!(key_order(&[0x62, 0x61, 0x62], &[0x62, 0x61, 0x61]), Ordering::Greater);
+	assert_eq!(key_order(&[0x20], &[0x0A]), Ordering::Greater);
+	assert_eq!(key_order(&[0x61, 0x61], &[0x61, 0x61]), Ordering::Equal);
+
+	let mut keys: [&[u8]; 4] = [&[0x62, 0x61, 0x61], &[0x18, 0x64], &[0x20], &[0x0A]];
+	keys.sort_by(|a, b| key_order(a, b));
+	assert!(keys == [&[0x0A][..], &[0x20], &[0x18, 0x64], &[0x62, 0x61, 0x61]]);
+    }
+
+    #[test]
+    fn test_simple_initial_byte() {
+	const SENTINEL: [u8; 3] = [
+	    simple_initial_byte(SIMPLE_VALUE_FALSE),
+	    simple_initial_byte(SIMPLE_VALUE_TRUE),
+	    simple_initial_byte(0)
+	];
+
+	assert_eq!(SENTINEL, [0xF4, 0xF5, 0xE0]);
+	assert_eq!(simple_initial_byte(23), 0xF7);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_simple_initial_byte_out_of_range() {
+	simple_initial_byte(24);
+    }
+
+    #[test]
+    fn test_simple_value() {
+	assert_eq!(SimpleValue::from_u8(20), SimpleValue::False);
+	assert_eq!(SimpleValue::from_u8(21), SimpleValue::True);
+	assert_eq!(SimpleValue::from_u8(22), SimpleValue::Null);
+	assert_eq!(SimpleValue::from_u8(23), SimpleValue::Undefined);
+	assert_eq!(SimpleValue::from_u8(99), SimpleValue::Other(99));
+
+	for val in 0..=0xFF_u8 {
+	    assert_eq!(SimpleValue::from_u8(val).to_u8(), val);
+	}
+
+	assert_eq!(Event::Simple(21).simple_value(), Some(SimpleValue::True));
+	assert_eq!(Event::Simple(99).simple_value(), Some(SimpleValue::Other(99)));
+	assert_eq!(Event::UnsignedInteger(21).simple_value(), None);
+    }
+
+    #[test]
+    fn test_bignum_to_i128() {
+	assert_eq!(bignum_to_i128(2, &[0x01, 0x00, 0x00, 0x00, 0x00]), Some(0x01_0000_0000));
+	assert_eq!(bignum_to_i128(3, &[0x01, 0x00, 0x00, 0x00, 0x00]), Some(-0x01_0000_0001));
+	assert_eq!(bignum_to_i128(2, &[]), Some(0));
+	assert_eq!(bignum_to_i128(3, &[0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]), Some(i128::MIN));
+	assert_eq!(bignum_to_i128(2, &[0x00, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]), Some(i128::MAX));
+	assert_eq!(bignum_to_i128(2, &[0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]), None);
+	assert_eq!(bignum_to_i128(2, &[0x01; 17]), None);
+	assert_eq!(bignum_to_i128(4, &[0x01]), None);
+    }
+
+    #[test]
+    fn test_epoch_from_events() {
+	assert_eq!(epoch_from_events(1, &Event::UnsignedInteger(1363896240)), Some(1363896240.0));
+	assert_eq!(epoch_from_events(1, &Event::NegativeInteger(9)), Some(-10.0));
+	assert_eq!(epoch_from_events(1, &Event::DoubleFloat(&1363896240.5_f64.to_be_bytes())), Some(1363896240.5));
+	assert_eq!(epoch_from_events(1, &Event::HalfFloat(&[0x3E, 0x00])), Some(1.5));
+	assert_eq!(epoch_from_events(1, &Event::TextString(b"2013-03-21T20:04:00Z")), None);
+	assert_eq!(epoch_from_events(0, &Event::UnsignedInteger(1363896240)), None);
+    }
+
+    #[test]
+    fn test_f64_to_half() {
+	assert_eq!(f64_to_half(0.0), Some([0x00, 0x00]));
+	assert_eq!(f64_to_half(-0.0), Some([0x80, 0x00]));
+	assert_eq!(f64_to_half(1.0), Some([0x3C, 0x00]));
+	assert_eq!(f64_to_half(-4.0), Some([0xC4, 0x00]));
+	assert_eq!(f64_to_half(65504.0), Some([0x7B, 0xFF]));
+	assert_eq!(f64_to_half(5.960464477539063e-8), Some([0x00, 0x01]));
+	assert_eq!(f64_to_half(0.00006103515625), Some([0x04, 0x00]));
+	assert_eq!(f64_to_half(f64::INFINITY), Some([0x7C, 0x00]));
+	assert_eq!(f64_to_half(f64::NAN), Some([0x7E, 0x00]));
+	assert_eq!(f64_to_half(65536.0), None);
+	assert_eq!(f64_to_half(1.1), None);
+	assert_eq!(f64_to_half(2.9802322387695312e-8), None);
+	assert_eq!(f64_to_half(100000.0), None);
+    }
+
+    #[test]
+    fn test_half_to_f64() {
+	assert_eq!(half_to_f64(&[0x00, 0x00]), 0.0);
+	assert_eq!(half_to_f64(&[0x3C, 0x00]), 1.0);
+	assert_eq!(half_to_f64(&[0xC4, 0x00]), -4.0);
+	assert_eq!(half_to_f64(&[0x7B, 0xFF]), 65504.0);
+	assert_eq!(half_to_f64(&[0x00, 0x01]), 5.960464477539063e-8);
+	assert_eq!(half_to_f64(&[0x7C, 0x00]), f64::INFINITY);
+	assert!(half_to_f64(&[0x7E, 0x00]).is_nan());
+    }
+    
+}
@@ -0,0 +1,72 @@
+
+use crate::decode;
+use crate::decode::Result;
+
+pub use crate::decode::{Head, MajorType};
+
+/// バイト列の先頭からデータ項目の先頭部分をデコードする。先頭部分と消費したバイト数を返す。
+pub fn decode_head(data: &[u8]) -> Result<(Head<'_>, usize)> {
+    let (head, rest) = decode::decode_head(data)?;
+
+    Ok((head, data.len() - rest.len()))
+}
+
+/// 引数を最短でエンコードした場合の、最初のバイトに続くバイト数(0・1・2・4・8のいずれか)を返す。
+pub fn minimal_arg_width(arg: u64) -> u8 {
+    if arg < 24 {
+	0
+    } else if arg <= 0xFF {
+	1
+    } else if arg <= 0xFFFF {
+	2
+    } else if arg <= 0xFFFF_FFFF {
+	4
+    } else {
+	8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::Error;
+
+    #[test]
+    fn test_decode_head() {
+	let (head, len) = decode_head(&[0x19, 0x01, 0x2C, 0x00]).unwrap();
+	assert_eq!(len, 3);
+	assert_eq!(head.major_type(), 0x00);
+	assert_eq!(head.additional_information(), 25);
+	assert_eq!(head.argument(), Some(300));
+	assert!(head.is_sound());
+
+	let (head, len) = decode_head(&[0x9F, 0x01]).unwrap();
+	assert_eq!(len, 1);
+	assert_eq!(head.major_type(), 0x80);
+	assert_eq!(head.major_type_enum(), MajorType::Array);
+	assert_eq!(head.argument(), None);
+
+	let (head, len) = decode_head(&[0x1F]).unwrap();
+	assert_eq!(len, 1);
+	assert!(!head.is_sound());
+
+	assert_eq!(decode_head(&[0x1C]), Err(Error::ReservedAdditionalInfo));
+	assert_eq!(decode_head(&[0x1A, 0x00]), Err(Error::UnexpectedEnd));
+	assert_eq!(decode_head(&[]), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_minimal_arg_width() {
+	assert_eq!(minimal_arg_width(0), 0);
+	assert_eq!(minimal_arg_width(23), 0);
+	assert_eq!(minimal_arg_width(24), 1);
+	assert_eq!(minimal_arg_width(255), 1);
+	assert_eq!(minimal_arg_width(256), 2);
+	assert_eq!(minimal_arg_width(65535), 2);
+	assert_eq!(minimal_arg_width(65536), 4);
+	assert_eq!(minimal_arg_width(0xFFFF_FFFF), 4);
+	assert_eq!(minimal_arg_width(0x1_0000_0000), 8);
+	assert_eq!(minimal_arg_width(u64::MAX), 8);
+    }
+
+}
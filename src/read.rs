@@ -0,0 +1,239 @@
+use std::fmt;
+use std::result;
+use std::io;
+use std::io::Read;
+
+use crate::event::*;
+use crate::decode;
+use crate::decode::{check_chunk, decode_event, decode_head, following_bytes_len};
+
+/// `io::Read`からのデコード時に発生するエラー。
+#[derive(Debug)]
+pub enum Error {
+    /// 入出力エラー。
+    IoError(io::Error),
+
+    /// CBORデータが不正である場合。
+    Decode(decode::Error)
+}
+
+impl From<decode::Error> for Error {
+    fn from(err: decode::Error) -> Error {
+	Error::Decode(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    Error::IoError(err) => write!(f, "I/O error: {}", err),
+	    Error::Decode(err) => write!(f, "decode error: {:?}", err)
+	}
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+	match self {
+	    Error::IoError(err) => Some(err),
+	    Error::Decode(_) => None
+	}
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// `io::Read`から必要な分だけ読み込みながらデコードするデコーダー型。
+pub struct ReadDecoder<R: Read> {
+    reader: R,
+    chunk_type: Option<u8>
+}
+
+impl<R: Read> ReadDecoder<R> {
+
+    /// デコーダーを作成する。パラメーターは読み込み元。
+    pub fn new(reader: R) -> ReadDecoder<R> {
+	ReadDecoder {
+	    reader,
+	    chunk_type: None
+	}
+    }
+
+    /// 読み込み元を取り出す。
+    pub fn into_inner(self) -> R {
+	self.reader
+    }
+
+    fn read_initial_byte(&mut self) -> Result<Option<u8>> {
+	let mut buf = [0_u8; 1];
+
+	loop {
+	    match self.reader.read(&mut buf) {
+		Ok(0) => return Ok(None),
+		Ok(_) => return Ok(Some(buf[0])),
+		Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+		Err(err) => return Err(Error::IoError(err))
+	    }
+	}
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+	match self.reader.read_exact(buf) {
+	    Ok(()) => Ok(()),
+	    Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Err(Error::Decode(decode::Error::UnexpectedEnd)),
+	    Err(err) => Err(Error::IoError(err))
+	}
+    }
+
+    fn read_content(&mut self, len: u64, content: &mut Vec<u8>) -> Result<()> {
+	if let Err(err) = (&mut self.reader).take(len).read_to_end(content) {
+	    return Err(Error::IoError(err));
+	}
+
+	if content.len() as u64 == len {
+	    Ok(())
+	} else {
+	    Err(Error::Decode(decode::Error::UnexpectedEnd))
+	}
+    }
+
+    /// 次のイベントを取得する。
+    pub fn decode_event(&mut self) -> Result<OwnedEvent> {
+	let mut scratch = Vec::new();
+
+	Ok(OwnedEvent::from(self.decode_event_with(&mut scratch)?))
+    }
+
+    /// 次のイベントを、内容を`scratch`に読み込んで取得する。
+    ///
+    /// `scratch`は呼び出しごとに空にしてから使うため、同じバッファーを繰り返し渡すと確保済みの領域が再利用される。
+    pub fn decode_event_with<'s>(&mut self, scratch: &'s mut Vec<u8>) -> Result<Event<'s>> {
+	let mut buf = [0_u8; 9];
+	scratch.clear();
+
+	buf[0] = match self.read_initial_byte()? {
+	    Some(ib) => ib,
+	    None => return Ok(Event::End)
+	};
+
+	let len = 1 + following_bytes_len(buf[0])?;
+	self.read_exact(&mut buf[1..len])?;
+
+	let (head, _) = decode_head(&buf[..len])?;
+
+	let event = match (head.major_type(), head.argument()) {
+	    (0x40, Some(arg)) => {
+		self.read_content(arg, scratch)?;
+		Event::ByteString(scratch)
+	    },
+	    (0x60, Some(arg)) => {
+		self.read_content(arg, scratch)?;
+		Event::TextString(scratch)
+	    },
+	    _ => {
+		scratch.extend_from_slice(&buf[..len]);
+		decode_event(scratch)?.0
+	    }
+	};
+
+	self.chunk_type = check_chunk(self.chunk_type, &event)?;
+
+	Ok(event)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    struct OneByteReader<R: Read>(R);
+
+    impl<R: Read> Read for OneByteReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+	    let len = buf.len().min(1);
+	    self.0.read(&mut buf[..len])
+	}
+    }
+
+    #[test]
+    fn test_read_decoder() {
+	let mut dec = ReadDecoder::new(OneByteReader(Cursor::new([
+	    0xA2,
+	    0x65, 0x68, 0x65, 0x6C, 0x6C, 0x6F, 0x19, 0x01, 0x2C,
+	    0x5F, 0x42, 0x01, 0x02, 0xFF,
+	    0x39, 0x01, 0x00,
+	    0xFA, 0x3F, 0xC0, 0x00, 0x00
+	])));
+
+	assert_eq!(dec.decode_event().unwrap(), OwnedEvent::Map(2));
+	assert_eq!(dec.decode_event().unwrap(), OwnedEvent::TextString(b"hello".to_vec()));
+	assert_eq!(dec.decode_event().unwrap(), OwnedEvent::UnsignedInteger(300));
+	assert_eq!(dec.decode_event().unwrap(), OwnedEvent::IndefiniteByteString);
+	assert_eq!(dec.decode_event().unwrap(), OwnedEvent::ByteString(vec![0x01, 0x02]));
+	assert_eq!(dec.decode_event().unwrap(), OwnedEvent::Break);
+	assert_eq!(dec.decode_event().unwrap(), OwnedEvent::NegativeInteger(256));
+	assert_eq!(dec.decode_event().unwrap(), OwnedEvent::SingleFloat([0x3F, 0xC0, 0x00, 0x00]));
+	assert_eq!(dec.decode_event().unwrap(), OwnedEvent::End);
+    }
+
+    #[test]
+    fn test_decode_event_with() {
+	let mut data = vec![0x99, 0x03, 0xE8];
+	for i in 0..1000_u32 {
+	    data.push(0x44);
+	    data.extend_from_slice(&i.to_be_bytes());
+	}
+	data.extend_from_slice(&[0xF9, 0x3C, 0x00]);
+
+	let mut dec = ReadDecoder::new(Cursor::new(data));
+	let mut scratch = Vec::with_capacity(16);
+	let ptr = scratch.as_ptr();
+
+	assert_eq!(dec.decode_event_with(&mut scratch).unwrap(), Event::Array(1000));
+	for i in 0..1000_u32 {
+	    assert_eq!(dec.decode_event_with(&mut scratch).unwrap(), Event::ByteString(&i.to_be_bytes()));
+	    assert_eq!(scratch.capacity(), 16);
+	    assert_eq!(scratch.as_ptr(), ptr);
+	}
+	assert_eq!(dec.decode_event_with(&mut scratch).unwrap(), Event::HalfFloat(&[0x3C, 0x00]));
+	assert_eq!(dec.decode_event_with(&mut scratch).unwrap(), Event::End);
+	assert_eq!(scratch.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_read_decoder_err() {
+	let mut dec = ReadDecoder::new(Cursor::new([0x19, 0x01]));
+	assert!(matches!(dec.decode_event(), Err(Error::Decode(decode::Error::UnexpectedEnd))));
+
+	let mut dec = ReadDecoder::new(Cursor::new([0x63, 0x61, 0x62]));
+	assert!(matches!(dec.decode_event(), Err(Error::Decode(decode::Error::UnexpectedEnd))));
+
+	let mut dec = ReadDecoder::new(Cursor::new([0x5F, 0x01]));
+	assert!(matches!(dec.decode_event(), Ok(OwnedEvent::IndefiniteByteString)));
+	assert!(matches!(dec.decode_event(), Err(Error::Decode(decode::Error::InvalidChunk))));
+    }
+
+    #[test]
+    fn test_read_decoder_io_err() {
+	struct FailingReader;
+
+	impl Read for FailingReader {
+	    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+		Err(io::Error::other("failure"))
+	    }
+	}
+
+	let mut dec = ReadDecoder::new(FailingReader);
+	let err = dec.decode_event().unwrap_err();
+	assert!(matches!(err, Error::IoError(_)));
+	assert_eq!(err.to_string(), "I/O error: failure");
+	assert!(std::error::Error::source(&err).is_some());
+
+	let err: Box<dyn std::error::Error> = Box::new(Error::Decode(decode::Error::UnexpectedEnd));
+	assert_eq!(err.to_string(), "decode error: UnexpectedEnd");
+	assert!(err.source().is_none());
+    }
+
+}
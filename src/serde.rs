@@ -0,0 +1,777 @@
+use std::fmt;
+use std::io::Write;
+
+use ::serde::de;
+use ::serde::de::{Deserialize, IntoDeserializer, Visitor};
+use ::serde::ser;
+use ::serde::ser::Serialize;
+
+use crate::event::*;
+use crate::encode;
+use crate::encode::{Encoder, IoSink};
+use crate::decode;
+use crate::decode::Decoder;
+use crate::misc::*;
+
+/// serdeとの連携で発生するエラー。
+#[derive(Debug)]
+pub enum Error {
+    /// エンコード時のエラー。
+    Encode(encode::Error),
+
+    /// デコード時のエラー。
+    Decode(decode::Error),
+
+    /// serdeが報告したエラー。
+    Message(String)
+}
+
+impl From<encode::Error> for Error {
+    fn from(err: encode::Error) -> Error {
+	Error::Encode(err)
+    }
+}
+
+impl From<decode::Error> for Error {
+    fn from(err: decode::Error) -> Error {
+	Error::Decode(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    Error::Encode(err) => write!(f, "encode error: {:?}", err),
+	    Error::Decode(err) => write!(f, "decode error: {:?}", err),
+	    Error::Message(msg) => f.write_str(msg)
+	}
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+	Error::Message(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+	Error::Message(msg.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// `Encoder`を用いてCBORデータを出力するシリアライザー。
+pub struct Serializer<W: Write> {
+    encoder: Encoder<IoSink<W>>
+}
+
+impl<W: Write> Serializer<W> {
+
+    /// シリアライザーを作成する。
+    pub fn new(writer: W) -> Serializer<W> {
+	Serializer {
+	    encoder: Encoder::new(IoSink::new(writer))
+	}
+    }
+
+    fn encode_event(&mut self, event: &Event) -> Result<()> {
+	Ok(self.encoder.encode_event(event)?)
+    }
+
+    fn encode_i64(&mut self, v: i64) -> Result<()> {
+	if v < 0 {
+	    self.encode_event(&Event::NegativeInteger(!(v as u64)))
+	} else {
+	    self.encode_event(&Event::UnsignedInteger(v as u64))
+	}
+    }
+
+    fn encode_len(&mut self, len: Option<usize>, definite: fn(u64) -> Event<'static>, indefinite: Event) -> Result<bool> {
+	match len {
+	    Some(len) => {
+		self.encode_event(&definite(len as u64))?;
+		Ok(false)
+	    },
+	    None => {
+		self.encode_event(&indefinite)?;
+		Ok(true)
+	    }
+	}
+    }
+
+}
+
+/// 値をCBORデータとして書き出す。
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<()> {
+    value.serialize(&mut Serializer::new(writer))
+}
+
+/// 配列や連想配列の要素をシリアライズする型。
+pub struct Compound<'a, W: Write> {
+    ser: &'a mut Serializer<W>,
+    indefinite: bool
+}
+
+impl<'a, W: Write> Compound<'a, W> {
+
+    fn end(self) -> Result<()> {
+	if self.indefinite {
+	    self.ser.encode_event(&Event::Break)
+	} else {
+	    Ok(())
+	}
+    }
+
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a, W>;
+    type SerializeTuple = Compound<'a, W>;
+    type SerializeTupleStruct = Compound<'a, W>;
+    type SerializeTupleVariant = Compound<'a, W>;
+    type SerializeMap = Compound<'a, W>;
+    type SerializeStruct = Compound<'a, W>;
+    type SerializeStructVariant = Compound<'a, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+	self.encode_event(&Event::Simple(if v { SIMPLE_VALUE_TRUE } else { SIMPLE_VALUE_FALSE }))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+	self.encode_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+	self.encode_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+	self.encode_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+	self.encode_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+	self.encode_event(&Event::UnsignedInteger(v as u64))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+	self.encode_event(&Event::UnsignedInteger(v as u64))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+	self.encode_event(&Event::UnsignedInteger(v as u64))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+	self.encode_event(&Event::UnsignedInteger(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+	self.encode_event(&Event::SingleFloat(&v.to_be_bytes()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+	self.encode_event(&Event::DoubleFloat(&v.to_be_bytes()))
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+	let mut buf = [0; 4];
+	self.serialize_str(v.encode_utf8(&mut buf))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+	self.encode_event(&Event::TextString(v.as_bytes()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+	self.encode_event(&Event::ByteString(v))
+    }
+
+    fn serialize_none(self) -> Result<()> {
+	self.encode_event(&Event::Simple(SIMPLE_VALUE_NULL))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+	value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+	self.encode_event(&Event::Simple(SIMPLE_VALUE_NULL))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+	self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> Result<()> {
+	self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<()> {
+	value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _variant_index: u32, variant: &'static str, value: &T) -> Result<()> {
+	self.encode_event(&Event::Map(1))?;
+	self.encode_event(&Event::TextString(variant.as_bytes()))?;
+	value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a, W>> {
+	let indefinite = self.encode_len(len, Event::Array, Event::IndefiniteArray)?;
+	Ok(Compound { ser: self, indefinite })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a, W>> {
+	self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a, W>> {
+	self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str, len: usize) -> Result<Compound<'a, W>> {
+	self.encode_event(&Event::Map(1))?;
+	self.encode_event(&Event::TextString(variant.as_bytes()))?;
+	self.serialize_seq(Some(len))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound<'a, W>> {
+	let indefinite = self.encode_len(len, Event::Map, Event::IndefiniteMap)?;
+	Ok(Compound { ser: self, indefinite })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a, W>> {
+	self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str, len: usize) -> Result<Compound<'a, W>> {
+	self.encode_event(&Event::Map(1))?;
+	self.encode_event(&Event::TextString(variant.as_bytes()))?;
+	self.serialize_map(Some(len))
+    }
+}
+
+impl<'a, W: Write> ser::SerializeSeq for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+	value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+	Compound::end(self)
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTuple for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+	value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+	Compound::end(self)
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTupleStruct for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+	value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+	Compound::end(self)
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTupleVariant for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+	value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+	Compound::end(self)
+    }
+}
+
+impl<'a, W: Write> ser::SerializeMap for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+	key.serialize(&mut *self.ser)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+	value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+	Compound::end(self)
+    }
+}
+
+impl<'a, W: Write> ser::SerializeStruct for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+	key.serialize(&mut *self.ser)?;
+	value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+	Compound::end(self)
+    }
+}
+
+impl<'a, W: Write> ser::SerializeStructVariant for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+	key.serialize(&mut *self.ser)?;
+	value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+	Compound::end(self)
+    }
+}
+
+/// `Decoder`を用いてCBORデータを読み込むデシリアライザー。
+pub struct Deserializer<'de> {
+    decoder: Decoder<'de>,
+    depth: usize
+}
+
+impl<'de> Deserializer<'de> {
+
+    /// デシリアライザーを作成する。パラメーターはデコード対象のバイト列。
+    pub fn new(data: &'de [u8]) -> Deserializer<'de> {
+	Deserializer {
+	    decoder: Decoder::new(data),
+	    depth: 0
+	}
+    }
+
+    fn decode_event(&mut self) -> Result<Event<'de>> {
+	Ok(self.decoder.decode_event()?)
+    }
+
+    fn peek_event(&self) -> Result<Event<'de>> {
+	Ok(self.decoder.peek_event()?)
+    }
+
+    fn skip_break(&mut self) -> Result<bool> {
+	if self.peek_event()? == Event::Break {
+	    self.decode_event()?;
+	    Ok(true)
+	} else {
+	    Ok(false)
+	}
+    }
+
+    fn enter(&mut self) -> Result<()> {
+	self.depth += 1;
+	if self.depth >= decode::MAX_NESTING_DEPTH {
+	    Err(Error::Decode(decode::Error::NestingTooDeep))
+	} else {
+	    Ok(())
+	}
+    }
+
+    fn visit_compound<V: Visitor<'de>>(&mut self, len: Option<u64>, map: bool, visitor: V) -> Result<V::Value> {
+	self.enter()?;
+
+	let mut access = Access { de: self, remaining: len };
+	let value = if map {
+	    visitor.visit_map(&mut access)?
+	} else {
+	    visitor.visit_seq(&mut access)?
+	};
+
+	match access.remaining {
+	    Some(0) => {},
+	    Some(_) => return Err(de::Error::custom("trailing elements")),
+	    None => if !self.skip_break()? {
+		return Err(de::Error::custom("trailing elements"));
+	    }
+	}
+
+	self.depth -= 1;
+
+	Ok(value)
+    }
+
+}
+
+/// CBORデータから値を読み込む。
+pub fn from_slice<'de, T: Deserialize<'de>>(data: &'de [u8]) -> Result<T> {
+    let mut deserializer = Deserializer::new(data);
+    let value = T::deserialize(&mut deserializer)?;
+
+    if deserializer.decode_event()? == Event::End {
+	Ok(value)
+    } else {
+	Err(de::Error::custom("trailing data"))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+	match self.decode_event()? {
+	    Event::UnsignedInteger(val) => visitor.visit_u64(val),
+	    Event::NegativeInteger(val) => if let Ok(val) = i64::try_from(val) {
+		visitor.visit_i64(-1 - val)
+	    } else {
+		visitor.visit_i128(-1 - val as i128)
+	    },
+	    Event::ByteString(content) => visitor.visit_borrowed_bytes(content),
+	    Event::TextString(content) => match std::str::from_utf8(content) {
+		Ok(text) => visitor.visit_borrowed_str(text),
+		Err(_) => Err(Error::Decode(decode::Error::InvalidUtf8))
+	    },
+	    Event::IndefiniteByteString => visitor.visit_byte_buf(self.decoder.read_indefinite_bytes()?),
+	    Event::IndefiniteTextString => visitor.visit_string(self.decoder.read_indefinite_text()?),
+	    Event::Array(len) => self.visit_compound(Some(len), false, visitor),
+	    Event::Map(len) => self.visit_compound(Some(len), true, visitor),
+	    Event::IndefiniteArray => self.visit_compound(None, false, visitor),
+	    Event::IndefiniteMap => self.visit_compound(None, true, visitor),
+	    Event::Tag(_) => {
+		self.enter()?;
+		let value = self.deserialize_any(visitor)?;
+		self.depth -= 1;
+		Ok(value)
+	    },
+	    Event::Simple(SIMPLE_VALUE_FALSE) => visitor.visit_bool(false),
+	    Event::Simple(SIMPLE_VALUE_TRUE) => visitor.visit_bool(true),
+	    Event::Simple(SIMPLE_VALUE_NULL) | Event::Simple(SIMPLE_VALUE_UNDEFINED) => visitor.visit_unit(),
+	    Event::Simple(val) => Err(de::Error::invalid_type(de::Unexpected::Unsigned(val as u64), &visitor)),
+	    Event::HalfFloat(bytes) => visitor.visit_f32(half_to_f64(bytes) as f32),
+	    Event::SingleFloat(bytes) => visitor.visit_f32(f32::from_be_bytes(*bytes)),
+	    Event::DoubleFloat(bytes) => visitor.visit_f64(f64::from_be_bytes(*bytes)),
+	    Event::Float(val) => visitor.visit_f64(val),
+	    Event::Break => Err(Error::Decode(decode::Error::UnexpectedBreak)),
+	    Event::End => Err(Error::Decode(decode::Error::UnexpectedEnd))
+	}
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+	match self.peek_event()? {
+	    Event::Simple(SIMPLE_VALUE_NULL) | Event::Simple(SIMPLE_VALUE_UNDEFINED) => {
+		self.decode_event()?;
+		visitor.visit_none()
+	    },
+	    _ => visitor.visit_some(self)
+	}
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+	visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
+	match self.peek_event()? {
+	    Event::TextString(_) | Event::IndefiniteTextString => {
+		let variant = String::deserialize(&mut *self)?;
+		visitor.visit_enum(variant.into_deserializer())
+	    },
+	    Event::Map(1) => {
+		self.decode_event()?;
+		self.enter()?;
+		let value = visitor.visit_enum(&mut *self)?;
+		self.depth -= 1;
+		Ok(value)
+	    },
+	    _ => Err(de::Error::custom("expected enum"))
+	}
+    }
+
+    ::serde::forward_to_deserialize_any! {
+	bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+	bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+	identifier ignored_any
+    }
+}
+
+struct Access<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    remaining: Option<u64>
+}
+
+impl<'a, 'de> Access<'a, 'de> {
+
+    fn has_next(&mut self) -> Result<bool> {
+	match self.remaining {
+	    Some(0) => Ok(false),
+	    Some(len) => {
+		self.remaining = Some(len - 1);
+		Ok(true)
+	    },
+	    None => Ok(self.de.peek_event()? != Event::Break)
+	}
+    }
+
+    // 残りのバイト列より多くの要素は無いので、宣言された要素数が巨大でも残りのバイト数で抑える。
+    fn size_hint(&self) -> Option<usize> {
+	let len = self.remaining?.min(self.de.decoder.remaining().len() as u64);
+	usize::try_from(len).ok()
+    }
+
+}
+
+impl<'a, 'de> de::SeqAccess<'de> for Access<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+	if self.has_next()? {
+	    seed.deserialize(&mut *self.de).map(Some)
+	} else {
+	    Ok(None)
+	}
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+	self.size_hint()
+    }
+}
+
+impl<'a, 'de> de::MapAccess<'de> for Access<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+	if self.has_next()? {
+	    seed.deserialize(&mut *self.de).map(Some)
+	} else {
+	    Ok(None)
+	}
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+	seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+	self.size_hint()
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+	let variant = seed.deserialize(&mut *self)?;
+	Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+	de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+	seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+	de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+	de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::decode::Decoder;
+
+    #[test]
+    fn test_serialize_map() {
+	let mut map = HashMap::new();
+	map.insert(String::from("a"), 1_i64);
+	map.insert(String::from("bc"), -300_i64);
+
+	let mut buf = Vec::new();
+	to_writer(&mut buf, &map).unwrap();
+
+	let mut dec = Decoder::new(&buf);
+	let mut decoded = HashMap::new();
+
+	assert_eq!(dec.decode_event(), Ok(Event::Map(2)));
+	for _ in 0..2 {
+	    let key = match dec.decode_event() {
+		Ok(Event::TextString(key)) => String::from_utf8(key.to_vec()).unwrap(),
+		other => panic!("unexpected {:?}", other)
+	    };
+	    let val = match dec.decode_event() {
+		Ok(Event::UnsignedInteger(val)) => val as i64,
+		Ok(Event::NegativeInteger(val)) => -1 - val as i64,
+		other => panic!("unexpected {:?}", other)
+	    };
+	    decoded.insert(key, val);
+	}
+	assert_eq!(dec.decode_event(), Ok(Event::End));
+
+	assert_eq!(decoded, map);
+    }
+
+    #[test]
+    fn test_serialize_struct() {
+	#[derive(::serde::Serialize)]
+	struct Point {
+	    x: u8,
+	    y: Option<bool>,
+	    tags: Vec<&'static str>
+	}
+
+	let mut buf = Vec::new();
+	to_writer(&mut buf, &Point { x: 1, y: None, tags: vec!["t"] }).unwrap();
+
+	assert_eq!(buf, [
+	    0xA3,
+	    0x61, 0x78, 0x01,
+	    0x61, 0x79, 0xF6,
+	    0x64, 0x74, 0x61, 0x67, 0x73, 0x81, 0x61, 0x74
+	]);
+    }
+
+    #[test]
+    fn test_serialize_indefinite() {
+	struct Counter(u8);
+
+	impl Serialize for Counter {
+	    fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+		use ::serde::ser::SerializeSeq;
+		let mut seq = serializer.serialize_seq(None)?;
+		for i in 0..self.0 {
+		    seq.serialize_element(&i)?;
+		}
+		seq.end()
+	    }
+	}
+
+	let mut buf = Vec::new();
+	to_writer(&mut buf, &Counter(2)).unwrap();
+
+	assert_eq!(buf, [0x9F, 0x00, 0x01, 0xFF]);
+    }
+
+    #[test]
+    fn test_deserialize_vec() {
+	let v: Vec<i64> = from_slice(&[0x83, 0x01, 0x38, 0x63, 0x1B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]).unwrap();
+	assert_eq!(v, [1, -100, 0x1_0000_0000]);
+
+	let v: Vec<i64> = from_slice(&[0x9F, 0x01, 0x02, 0xFF]).unwrap();
+	assert_eq!(v, [1, 2]);
+
+	assert!(from_slice::<Vec<i64>>(&[0x83, 0x01, 0x02]).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_huge_length() {
+	use std::cell::Cell;
+
+	struct SizeHint<'c>(&'c Cell<Option<usize>>);
+
+	impl<'de, 'c> Visitor<'de> for SizeHint<'c> {
+	    type Value = ();
+
+	    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("a sequence")
+	    }
+
+	    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+		self.0.set(seq.size_hint());
+		while seq.next_element::<u8>()?.is_some() {}
+		Ok(())
+	    }
+	}
+
+	let data = [0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x02];
+	assert!(from_slice::<Vec<u8>>(&data).is_err());
+
+	let hint = Cell::new(None);
+	assert!(de::Deserializer::deserialize_seq(&mut Deserializer::new(&data), SizeHint(&hint)).is_err());
+	assert_eq!(hint.get(), Some(2));
+    }
+
+    #[test]
+    fn test_deserialize_struct() {
+	#[derive(::serde::Deserialize, PartialEq, Debug)]
+	enum Kind {
+	    Plain,
+	    Scaled(u8)
+	}
+
+	#[derive(::serde::Deserialize, PartialEq, Debug)]
+	struct Record<'a> {
+	    name: &'a str,
+	    data: &'a [u8],
+	    ratio: f64,
+	    note: Option<String>,
+	    kinds: Vec<Kind>
+	}
+
+	let data = [
+	    0xBF,
+	    0x64, 0x6E, 0x61, 0x6D, 0x65, 0x62, 0x61, 0x62,
+	    0x64, 0x64, 0x61, 0x74, 0x61, 0x42, 0x01, 0x02,
+	    0x65, 0x72, 0x61, 0x74, 0x69, 0x6F, 0xF9, 0x3E, 0x00,
+	    0x64, 0x6E, 0x6F, 0x74, 0x65, 0xF6,
+	    0x65, 0x6B, 0x69, 0x6E, 0x64, 0x73, 0x82,
+	    0x65, 0x50, 0x6C, 0x61, 0x69, 0x6E,
+	    0xA1, 0x66, 0x53, 0x63, 0x61, 0x6C, 0x65, 0x64, 0x05,
+	    0xFF
+	];
+	let record: Record = from_slice(&data).unwrap();
+
+	assert_eq!(record, Record {
+	    name: "ab",
+	    data: &[0x01, 0x02],
+	    ratio: 1.5,
+	    note: None,
+	    kinds: vec![Kind::Plain, Kind::Scaled(5)]
+	});
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+	let mut map = HashMap::new();
+	map.insert(String::from("x"), vec![Some(1_u32), None]);
+
+	let mut buf = Vec::new();
+	to_writer(&mut buf, &map).unwrap();
+
+	assert_eq!(from_slice::<HashMap<String, Vec<Option<u32>>>>(&buf).unwrap(), map);
+    }
+
+}
@@ -0,0 +1,58 @@
+
+/// 標準日時文字列を表すタグ。
+pub const STANDARD_DATETIME: u64 = 0;
+
+/// エポックからの秒数で日時を表すタグ。
+pub const EPOCH_DATETIME: u64 = 1;
+
+/// 正の多倍長整数を表すタグ。
+pub const POSITIVE_BIGNUM: u64 = 2;
+
+/// 負の多倍長整数を表すタグ。
+pub const NEGATIVE_BIGNUM: u64 = 3;
+
+/// 十進小数を表すタグ。
+pub const DECIMAL_FRACTION: u64 = 4;
+
+/// 二進浮動小数点数を表すタグ。
+pub const BIGFLOAT: u64 = 5;
+
+/// base64url符号化が期待されることを表すタグ。
+pub const EXPECTED_BASE64URL: u64 = 21;
+
+/// base64符号化が期待されることを表すタグ。
+pub const EXPECTED_BASE64: u64 = 22;
+
+/// base16符号化が期待されることを表すタグ。
+pub const EXPECTED_BASE16: u64 = 23;
+
+/// CBORデータを埋め込んだバイト列を表すタグ。
+pub const ENCODED_CBOR: u64 = 24;
+
+/// URIを表すタグ。
+pub const URI: u64 = 32;
+
+/// base64url文字列を表すタグ。
+pub const BASE64URL: u64 = 33;
+
+/// base64文字列を表すタグ。
+pub const BASE64: u64 = 34;
+
+/// MIMEメッセージを表すタグ。
+pub const MIME_MESSAGE: u64 = 36;
+
+/// 自己記述CBORを表すタグ。
+pub const SELF_DESCRIBED: u64 = 55799;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags() {
+	assert_eq!(EPOCH_DATETIME, 1);
+	assert_eq!(ENCODED_CBOR, 24);
+	assert_eq!(SELF_DESCRIBED, 0xD9F7);
+    }
+    
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::result;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::event::*;
+use crate::decode;
+use crate::decode::Decoder;
+use crate::encode;
+use crate::encode::{ByteSink, Encoder};
+use crate::misc::epoch_from_events;
+use crate::tags::EPOCH_DATETIME;
+
+/// 日時の変換で発生するエラー。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// デコード時のエラー。
+    Decode(decode::Error),
+
+    /// エポック日時タグ(1)と数値の組でない場合。
+    NotEpochTime,
+
+    /// 日時が`SystemTime`で表せる範囲を超えている場合や、NaN・無限大の場合。
+    OutOfRange
+}
+
+impl From<decode::Error> for Error {
+    fn from(err: decode::Error) -> Error {
+	Error::Decode(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    Error::Decode(err) => write!(f, "decode error: {:?}", err),
+	    Error::NotEpochTime => f.write_str("not an epoch-based date/time"),
+	    Error::OutOfRange => f.write_str("date/time is out of range")
+	}
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// エポック日時タグ(1)とその内容のイベントを`SystemTime`に変換する。
+///
+/// 整数は誤差なく変換し、浮動小数点数は小数部分も含めて変換する。エポックより前の日時も扱う。
+pub fn system_time_from_events(tag: u64, value: &Event) -> Result<SystemTime> {
+    if tag != EPOCH_DATETIME {
+	return Err(Error::NotEpochTime);
+    }
+
+    let time = match *value {
+	Event::UnsignedInteger(secs) => UNIX_EPOCH.checked_add(Duration::from_secs(secs)),
+	Event::NegativeInteger(val) => Duration::from_secs(val).checked_add(Duration::from_secs(1)).and_then(|duration| UNIX_EPOCH.checked_sub(duration)),
+	_ => {
+	    let secs = epoch_from_events(tag, value).ok_or(Error::NotEpochTime)?;
+	    let duration = Duration::try_from_secs_f64(secs.abs()).map_err(|_| Error::OutOfRange)?;
+
+	    if secs < 0.0 {
+		UNIX_EPOCH.checked_sub(duration)
+	    } else {
+		UNIX_EPOCH.checked_add(duration)
+	    }
+	}
+    };
+
+    time.ok_or(Error::OutOfRange)
+}
+
+/// エポック日時タグ(1)とその内容をデコードして`SystemTime`に変換する。
+pub fn decode_system_time(decoder: &mut Decoder) -> Result<SystemTime> {
+    match decoder.decode_event()? {
+	Event::Tag(tag) => match decoder.decode_event()? {
+	    Event::End => Err(Error::Decode(decode::Error::UnexpectedEnd)),
+	    value => system_time_from_events(tag, &value)
+	},
+	_ => Err(Error::NotEpochTime)
+    }
+}
+
+impl<W: ByteSink> Encoder<W> {
+
+    /// `SystemTime`をエポック日時タグ(1)とエポックからの秒数としてエンコードする。
+    ///
+    /// 秒数が整数の場合は整数、小数部分を持つ場合は浮動小数点数で出力する。エポックより前の日時は負の値になる。
+    pub fn encode_system_time(&mut self, time: SystemTime) -> encode::Result<()> {
+	self.encode_tag(EPOCH_DATETIME)?;
+
+	match time.duration_since(UNIX_EPOCH) {
+	    Ok(duration) => if duration.subsec_nanos() == 0 {
+		self.encode_event(&Event::UnsignedInteger(duration.as_secs()))
+	    } else {
+		self.encode_f64(duration.as_secs_f64())
+	    },
+	    Err(err) => {
+		let duration = err.duration();
+
+		if duration.subsec_nanos() == 0 {
+		    self.encode_event(&Event::NegativeInteger(duration.as_secs() - 1))
+		} else {
+		    self.encode_f64(-duration.as_secs_f64())
+		}
+	    }
+	}
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_time_from_events() {
+	assert_eq!(system_time_from_events(1, &Event::UnsignedInteger(0)), Ok(UNIX_EPOCH));
+	assert_eq!(system_time_from_events(1, &Event::UnsignedInteger(1_363_896_240)), Ok(UNIX_EPOCH + Duration::from_secs(1_363_896_240)));
+	assert_eq!(system_time_from_events(1, &Event::NegativeInteger(9)), Ok(UNIX_EPOCH - Duration::from_secs(10)));
+	assert_eq!(system_time_from_events(1, &Event::DoubleFloat(&1.5_f64.to_be_bytes())), Ok(UNIX_EPOCH + Duration::from_millis(1500)));
+	assert_eq!(system_time_from_events(1, &Event::HalfFloat(&[0xBC, 0x00])), Ok(UNIX_EPOCH - Duration::from_secs(1)));
+	assert_eq!(system_time_from_events(1, &Event::Float(-0.25)), Ok(UNIX_EPOCH - Duration::from_millis(250)));
+
+	assert_eq!(system_time_from_events(0, &Event::UnsignedInteger(0)), Err(Error::NotEpochTime));
+	assert_eq!(system_time_from_events(1, &Event::TextString(b"0")), Err(Error::NotEpochTime));
+	assert_eq!(system_time_from_events(1, &Event::Float(f64::NAN)), Err(Error::OutOfRange));
+	assert_eq!(system_time_from_events(1, &Event::Float(f64::INFINITY)), Err(Error::OutOfRange));
+	assert_eq!(system_time_from_events(1, &Event::Float(1e300)), Err(Error::OutOfRange));
+	assert_eq!(system_time_from_events(1, &Event::NegativeInteger(u64::MAX)), Err(Error::OutOfRange));
+    }
+
+    #[test]
+    fn test_decode_system_time() {
+	let mut dec = Decoder::new(&[0xC1, 0x00, 0xC1, 0xFB, 0x41, 0xD4, 0x52, 0xD9, 0xEC, 0x20, 0x00, 0x00, 0x01]);
+	assert_eq!(decode_system_time(&mut dec), Ok(UNIX_EPOCH));
+	assert_eq!(decode_system_time(&mut dec), Ok(UNIX_EPOCH + Duration::from_millis(1_363_896_240_500)));
+	assert_eq!(decode_system_time(&mut dec), Err(Error::NotEpochTime));
+	assert_eq!(decode_system_time(&mut dec), Err(Error::NotEpochTime));
+
+	let mut dec = Decoder::new(&[0xC1]);
+	assert_eq!(decode_system_time(&mut dec), Err(Error::Decode(decode::Error::UnexpectedEnd)));
+    }
+
+    #[test]
+    fn test_encode_system_time() {
+	let mut buf = Vec::<u8>::new();
+	let mut enc = Encoder::new(&mut buf);
+
+	let _ = enc.encode_system_time(UNIX_EPOCH + Duration::from_secs(5));
+	let _ = enc.encode_system_time(UNIX_EPOCH - Duration::from_secs(10));
+	let _ = enc.encode_system_time(UNIX_EPOCH + Duration::from_millis(1500));
+	let _ = enc.encode_system_time(UNIX_EPOCH - Duration::from_millis(250));
+
+	assert_eq!(buf, [
+	    0xC1, 0x05,
+	    0xC1, 0x29,
+	    0xC1, 0xF9, 0x3E, 0x00,
+	    0xC1, 0xF9, 0xB4, 0x00
+	]);
+
+	let mut dec = Decoder::new(&buf);
+	assert_eq!(decode_system_time(&mut dec), Ok(UNIX_EPOCH + Duration::from_secs(5)));
+	assert_eq!(decode_system_time(&mut dec), Ok(UNIX_EPOCH - Duration::from_secs(10)));
+	assert_eq!(decode_system_time(&mut dec), Ok(UNIX_EPOCH + Duration::from_millis(1500)));
+	assert_eq!(decode_system_time(&mut dec), Ok(UNIX_EPOCH - Duration::from_millis(250)));
+    }
+
+}
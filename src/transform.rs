@@ -0,0 +1,99 @@
+use core::fmt;
+use core::result;
+
+use crate::event::*;
+use crate::decode;
+use crate::decode::Decoder;
+use crate::encode;
+use crate::encode::{ByteSink, Encoder};
+
+/// 変換時に発生するエラー。
+#[derive(Debug)]
+pub enum Error {
+    /// デコード時のエラー。
+    Decode(decode::Error),
+
+    /// エンコード時のエラー。
+    Encode(encode::Error)
+}
+
+impl From<decode::Error> for Error {
+    fn from(err: decode::Error) -> Error {
+	Error::Decode(err)
+    }
+}
+
+impl From<encode::Error> for Error {
+    fn from(err: encode::Error) -> Error {
+	Error::Encode(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    Error::Decode(err) => write!(f, "decode error: {:?}", err),
+	    Error::Encode(err) => write!(f, "encode error: {}", err)
+	}
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// バイト列を`End`までデコードし、各イベントを`f`で変換してから出力先にエンコードする。
+///
+/// `f`は1つのイベントを1つのイベントに変換する。データ項目の構造を変えるような変換をすると、出力はCBORデータとして不正になる。
+pub fn transform<'a, W, F>(data: &'a [u8], writer: W, mut f: F) -> Result<()>
+where W: ByteSink, F: FnMut(Event<'a>) -> Event<'a> {
+    let mut dec = Decoder::new(data);
+    let mut enc = Encoder::new(writer);
+
+    loop {
+	match dec.decode_event()? {
+	    Event::End => return Ok(()),
+	    event => enc.encode_event(&f(event))?
+	}
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_transform() {
+	let data = [
+	    0xA2,
+	    0x61, 0x61, 0xD9, 0x03, 0xE8, 0x1A, 0x51, 0x4B, 0x67, 0xB0,
+	    0x61, 0x62, 0x82, 0xD9, 0x03, 0xE8, 0x00, 0xC2, 0x41, 0x01
+	];
+	let mut buf = Vec::<u8>::new();
+
+	let result = transform(&data, &mut buf, |event| match event {
+	    Event::Tag(1000) => Event::Tag(1),
+	    event => event
+	});
+
+	assert!(result.is_ok());
+	assert_eq!(buf, [
+	    0xA2,
+	    0x61, 0x61, 0xC1, 0x1A, 0x51, 0x4B, 0x67, 0xB0,
+	    0x61, 0x62, 0x82, 0xC1, 0x00, 0xC2, 0x41, 0x01
+	]);
+    }
+
+    #[test]
+    fn test_transform_err() {
+	let mut buf = Vec::<u8>::new();
+	assert!(matches!(transform(&[0x82, 0x01, 0x1C], &mut buf, |event| event), Err(Error::Decode(decode::Error::ReservedAdditionalInfo))));
+	assert_eq!(buf, [0x82, 0x01]);
+
+	let mut buf = Vec::<u8>::new();
+	assert!(matches!(transform(&[0x01], &mut buf, |_| Event::Simple(24)), Err(Error::Encode(encode::Error::ReservedSimpleValue))));
+    }
+
+}
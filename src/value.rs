@@ -0,0 +1,584 @@
+use core::fmt;
+use core::fmt::Write;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::event::*;
+use crate::decode::*;
+use crate::encode;
+use crate::encode::{ByteSink, Encoder};
+use crate::misc::*;
+use crate::diag::{write_bytes, write_float, write_text};
+
+/// 所有権を持つCBORデータ項目の木構造。
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    /// 整数。
+    Integer(i128),
+
+    /// バイト列。
+    Bytes(Vec<u8>),
+
+    /// 文字列。
+    Text(String),
+
+    /// 配列。
+    Array(Vec<Value>),
+
+    /// 連想配列。要素の順序はデータ中の順序に一致する。
+    Map(Vec<(Value, Value)>),
+
+    /// タグ付きの値。
+    Tag(u64, Box<Value>),
+
+    /// false・true・null・undefined以外の単純値。
+    Simple(u8),
+
+    /// 浮動小数点数。
+    Float(f64),
+
+    /// null。
+    Null,
+
+    /// undefined。
+    Undefined,
+
+    /// 真偽値。
+    Bool(bool)
+}
+
+/// `Value::pointer`に渡すパスの要素。
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PathSegment<'a> {
+    /// マップのキー。
+    Key(&'a Value),
+
+    /// 文字列であるマップのキー。
+    Text(&'a str),
+
+    /// 配列の添字。
+    Index(usize)
+}
+
+impl<'a> From<&'a Value> for PathSegment<'a> {
+    fn from(key: &'a Value) -> PathSegment<'a> {
+	PathSegment::Key(key)
+    }
+}
+
+impl<'a> From<&'a str> for PathSegment<'a> {
+    fn from(key: &'a str) -> PathSegment<'a> {
+	PathSegment::Text(key)
+    }
+}
+
+impl<'a> From<usize> for PathSegment<'a> {
+    fn from(index: usize) -> PathSegment<'a> {
+	PathSegment::Index(index)
+    }
+}
+
+fn is_break(decoder: &mut Decoder) -> Result<bool> {
+    match decoder.peek_event()? {
+	Event::Break => {
+	    decoder.decode_event()?;
+	    Ok(true)
+	},
+	_ => Ok(false)
+    }
+}
+
+/// 宣言された要素数と残りのバイト列の長さから、確保する容量を求める。各要素は少なくとも`item_len`バイトを占めるので、巨大な要素数を宣言されても残りのバイト列より多くは確保しない。
+fn capacity_hint(len: u64, remaining: &[u8], item_len: usize) -> usize {
+    usize::try_from(len).unwrap_or(usize::MAX).min(remaining.len() / item_len)
+}
+
+impl Value {
+
+    /// バイト列の先頭のデータ項目をデコードする。
+    pub fn decode(data: &[u8]) -> Result<Value> {
+	Value::from_events(&mut Decoder::new(data))
+    }
+
+    /// デコーダーから次のデータ項目を構成するイベントを読み込んで値を組み立てる。
+    pub fn from_events(decoder: &mut Decoder) -> Result<Value> {
+	Value::build(decoder, 0)
+    }
+
+    /// 値をエンコードする。配列と連想配列は固定長で、整数と浮動小数点数は値を変えない最短の形式で出力する。NaNは`F9 7E 00`とする。
+    pub fn encode<W: ByteSink>(&self, enc: &mut Encoder<W>) -> encode::Result<()> {
+	match self {
+	    Value::Integer(val) => enc.encode_i128(*val),
+	    Value::Bytes(content) => enc.encode_event(&Event::ByteString(content)),
+	    Value::Text(content) => enc.encode_event(&Event::TextString(content.as_bytes())),
+	    Value::Array(items) => {
+		enc.encode_event(&Event::Array(items.len() as u64))?;
+		for item in items {
+		    item.encode(enc)?;
+		}
+		Ok(())
+	    },
+	    Value::Map(entries) => {
+		enc.encode_event(&Event::Map(entries.len() as u64))?;
+		for (key, val) in entries {
+		    key.encode(enc)?;
+		    val.encode(enc)?;
+		}
+		Ok(())
+	    },
+	    Value::Tag(tag, content) => {
+		enc.encode_event(&Event::Tag(*tag))?;
+		content.encode(enc)
+	    },
+	    Value::Simple(val) => enc.encode_event(&Event::Simple(*val)),
+	    Value::Float(val) => enc.encode_f64(*val),
+	    Value::Null => enc.encode_event(&Event::Simple(SIMPLE_VALUE_NULL)),
+	    Value::Undefined => enc.encode_event(&Event::Simple(SIMPLE_VALUE_UNDEFINED)),
+	    Value::Bool(false) => enc.encode_event(&Event::Simple(SIMPLE_VALUE_FALSE)),
+	    Value::Bool(true) => enc.encode_event(&Event::Simple(SIMPLE_VALUE_TRUE))
+	}
+    }
+
+    /// 整数であればその値を返す。
+    pub fn as_integer(&self) -> Option<i128> {
+	match self {
+	    Value::Integer(val) => Some(*val),
+	    _ => None
+	}
+    }
+
+    /// 文字列であればその内容を返す。
+    pub fn as_text(&self) -> Option<&str> {
+	match self {
+	    Value::Text(content) => Some(content),
+	    _ => None
+	}
+    }
+
+    /// バイト列であればその内容を返す。
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+	match self {
+	    Value::Bytes(content) => Some(content),
+	    _ => None
+	}
+    }
+
+    /// 配列であればその要素を返す。
+    pub fn as_array(&self) -> Option<&[Value]> {
+	match self {
+	    Value::Array(items) => Some(items),
+	    _ => None
+	}
+    }
+
+    /// マップであればそのエントリーを返す。
+    pub fn as_map(&self) -> Option<&[(Value, Value)]> {
+	match self {
+	    Value::Map(entries) => Some(entries),
+	    _ => None
+	}
+    }
+
+    /// パスをたどって入れ子になった値を取得する。途中で型が一致しない場合や、キー・添字が存在しない場合は`None`を返す。
+    pub fn pointer(&self, path: &[PathSegment]) -> Option<&Value> {
+	let mut value = self;
+
+	for segment in path {
+	    value = match (segment, value) {
+		(PathSegment::Index(index), Value::Array(items)) => items.get(*index)?,
+		(PathSegment::Key(key), Value::Map(entries)) => &entries.iter().find(|(k, _)| k == *key)?.1,
+		(PathSegment::Text(key), Value::Map(entries)) => &entries.iter().find(|(k, _)| k.as_text() == Some(*key))?.1,
+		_ => return None
+	    };
+	}
+
+	Some(value)
+    }
+
+    /// 全てのマップのエントリーを、キーをエンコードしたバイト列の順序(RFC 8949 4.2.1節、短いものが先、同じ長さなら辞書順)に再帰的に並べ替える。
+    pub fn sort_keys(&mut self) {
+	match self {
+	    Value::Array(items) => {
+		for item in items {
+		    item.sort_keys();
+		}
+	    },
+	    Value::Map(entries) => {
+		for (key, val) in entries.iter_mut() {
+		    key.sort_keys();
+		    val.sort_keys();
+		}
+		entries.sort_by_cached_key(|(key, _)| {
+		    let mut buf = Vec::new();
+		    // Vec<u8>への書き込みは失敗しない。
+		    let _ = key.encode(&mut Encoder::new(&mut buf));
+		    (buf.len(), buf)
+		});
+	    },
+	    Value::Tag(_, content) => content.sort_keys(),
+	    _ => {}
+	}
+    }
+
+    fn build(decoder: &mut Decoder, depth: usize) -> Result<Value> {
+	if depth >= MAX_NESTING_DEPTH {
+	    return Err(Error::NestingTooDeep);
+	}
+
+	Ok(match decoder.decode_event()? {
+	    Event::UnsignedInteger(val) => Value::Integer(val as i128),
+	    Event::NegativeInteger(val) => Value::Integer(-1 - val as i128),
+	    Event::ByteString(content) => Value::Bytes(content.to_vec()),
+	    Event::TextString(content) => Value::Text(String::from(core::str::from_utf8(content).map_err(|_| Error::InvalidUtf8)?)),
+	    Event::IndefiniteByteString => Value::Bytes(decoder.read_indefinite_bytes()?),
+	    Event::IndefiniteTextString => Value::Text(decoder.read_indefinite_text()?),
+	    Event::Array(len) => {
+		let mut items = Vec::with_capacity(capacity_hint(len, decoder.remaining(), 1));
+		for _ in 0..len {
+		    items.push(Value::build(decoder, depth + 1)?);
+		}
+		Value::Array(items)
+	    },
+	    Event::Map(len) => {
+		let mut entries = Vec::with_capacity(capacity_hint(len, decoder.remaining(), 2));
+		for _ in 0..len {
+		    let key = Value::build(decoder, depth + 1)?;
+		    let val = Value::build(decoder, depth + 1)?;
+		    entries.push((key, val));
+		}
+		Value::Map(entries)
+	    },
+	    Event::IndefiniteArray => {
+		let mut items = Vec::new();
+		while !is_break(decoder)? {
+		    items.push(Value::build(decoder, depth + 1)?);
+		}
+		Value::Array(items)
+	    },
+	    Event::IndefiniteMap => {
+		let mut entries = Vec::new();
+		while !is_break(decoder)? {
+		    let key = Value::build(decoder, depth + 1)?;
+		    let val = Value::build(decoder, depth + 1)?;
+		    entries.push((key, val));
+		}
+		Value::Map(entries)
+	    },
+	    Event::Tag(tag) => Value::Tag(tag, Box::new(Value::build(decoder, depth + 1)?)),
+	    Event::Simple(SIMPLE_VALUE_FALSE) => Value::Bool(false),
+	    Event::Simple(SIMPLE_VALUE_TRUE) => Value::Bool(true),
+	    Event::Simple(SIMPLE_VALUE_NULL) => Value::Null,
+	    Event::Simple(SIMPLE_VALUE_UNDEFINED) => Value::Undefined,
+	    Event::Simple(val) => Value::Simple(val),
+	    Event::HalfFloat(bytes) => Value::Float(half_to_f64(bytes)),
+	    Event::SingleFloat(bytes) => Value::Float(f32::from_be_bytes(*bytes) as f64),
+	    Event::DoubleFloat(bytes) => Value::Float(f64::from_be_bytes(*bytes)),
+	    Event::Float(val) => Value::Float(val),
+	    Event::Break => return Err(Error::UnexpectedBreak),
+	    Event::End => return Err(Error::UnexpectedEnd)
+	})
+    }
+
+    fn write_diagnostic(&self, output: &mut String) {
+	match self {
+	    Value::Integer(val) => {
+		let _ = write!(output, "{}", val);
+	    },
+	    Value::Bytes(content) => write_bytes(output, content),
+	    Value::Text(content) => write_text(output, content),
+	    Value::Array(items) => {
+		output.push('[');
+		for (i, item) in items.iter().enumerate() {
+		    if i > 0 {
+			output.push_str(", ");
+		    }
+		    item.write_diagnostic(output);
+		}
+		output.push(']');
+	    },
+	    Value::Map(entries) => {
+		output.push('{');
+		for (i, (key, val)) in entries.iter().enumerate() {
+		    if i > 0 {
+			output.push_str(", ");
+		    }
+		    key.write_diagnostic(output);
+		    output.push_str(": ");
+		    val.write_diagnostic(output);
+		}
+		output.push('}');
+	    },
+	    Value::Tag(tag, content) => {
+		let _ = write!(output, "{}(", tag);
+		content.write_diagnostic(output);
+		output.push(')');
+	    },
+	    Value::Simple(val) => {
+		let _ = write!(output, "simple({})", val);
+	    },
+	    Value::Float(val) => write_float(output, *val),
+	    Value::Null => output.push_str("null"),
+	    Value::Undefined => output.push_str("undefined"),
+	    Value::Bool(val) => output.push_str(if *val { "true" } else { "false" })
+	}
+    }
+
+}
+
+impl fmt::Display for Value {
+    /// RFC 8949の診断記法で出力する。
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	let mut output = String::new();
+	self.write_diagnostic(&mut output);
+	f.write_str(&output)
+    }
+}
+
+/// ちょうど1つのデータ項目からなるバイト列をデコードする。後にデータが続いている場合は`TrailingData`エラーを返す。
+pub fn decode_single(data: &[u8]) -> Result<Value> {
+    let mut decoder = Decoder::new(data);
+    let value = Value::from_events(&mut decoder)?;
+
+    if decoder.remaining().is_empty() {
+	Ok(value)
+    } else {
+	Err(Error::TrailingData { offset: data.len() - decoder.remaining().len() })
+    }
+}
+
+impl TryFrom<&[u8]> for Value {
+    type Error = Error;
+
+    /// バイト列の先頭のデータ項目をデコードする。`Value::decode`と同じ。
+    fn try_from(data: &[u8]) -> Result<Value> {
+	Value::decode(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn encode_to_vec(value: &Value) -> Vec<u8> {
+	let mut buf = Vec::new();
+	let _ = value.encode(&mut Encoder::new(&mut buf));
+	buf
+    }
+
+    #[test]
+    fn test_display() {
+	use alloc::format;
+
+	let value = Value::Map(vec![
+	    (Value::Text(String::from("a")), Value::Array(vec![Value::Integer(-1), Value::Float(0.1), Value::Float(f64::NAN)])),
+	    (Value::Integer(1), Value::Tag(1, Box::new(Value::Float(1.0)))),
+	    (Value::Bytes(vec![0x01, 0xFF]), Value::Array(vec![Value::Null, Value::Undefined, Value::Bool(true), Value::Simple(99)]))
+	]);
+
+	assert_eq!(format!("{}", value), "{\"a\": [-1, 0.1, NaN], 1: 1(1.0), h'01ff': [null, undefined, true, simple(99)]}");
+    }
+
+    #[test]
+    fn test_accessors() {
+	let value = Value::Integer(-5);
+	assert_eq!(value.as_integer(), Some(-5));
+	assert_eq!(value.as_text(), None);
+
+	let value = Value::Text(String::from("abc"));
+	assert_eq!(value.as_text(), Some("abc"));
+	assert_eq!(value.as_bytes(), None);
+
+	let value = Value::Bytes(vec![0x01, 0x02]);
+	assert_eq!(value.as_bytes(), Some(&[0x01, 0x02][..]));
+	assert_eq!(value.as_integer(), None);
+
+	let value = Value::Array(vec![Value::Null]);
+	assert_eq!(value.as_array(), Some(&[Value::Null][..]));
+	assert_eq!(value.as_map(), None);
+
+	let value = Value::Map(vec![(Value::Integer(1), Value::Bool(true))]);
+	assert_eq!(value.as_map(), Some(&[(Value::Integer(1), Value::Bool(true))][..]));
+	assert_eq!(value.as_array(), None);
+    }
+
+    #[test]
+    fn test_pointer() {
+	let value = Value::Map(vec![
+	    (Value::Text(String::from("users")), Value::Array(vec![
+		Value::Map(vec![
+		    (Value::Text(String::from("name")), Value::Text(String::from("alice"))),
+		    (Value::Integer(1), Value::Bool(true))
+		])
+	    ]))
+	]);
+
+	let name = value.pointer(&["users".into(), 0.into(), "name".into()]);
+	assert_eq!(name, Some(&Value::Text(String::from("alice"))));
+
+	let key = Value::Integer(1);
+	assert_eq!(value.pointer(&["users".into(), 0.into(), (&key).into()]), Some(&Value::Bool(true)));
+	assert_eq!(value.pointer(&[]), Some(&value));
+
+	assert_eq!(value.pointer(&["users".into(), 1.into()]), None);
+	assert_eq!(value.pointer(&["users".into(), "name".into()]), None);
+	assert_eq!(value.pointer(&[0.into()]), None);
+	assert_eq!(value.pointer(&["groups".into()]), None);
+    }
+
+    #[test]
+    fn test_sort_keys() {
+	let mut a = Value::Map(vec![
+	    (Value::Text(String::from("bb")), Value::Integer(1)),
+	    (Value::Integer(100), Value::Map(vec![
+		(Value::Integer(-1), Value::Null),
+		(Value::Integer(10), Value::Null)
+	    ])),
+	    (Value::Text(String::from("a")), Value::Integer(2)),
+	    (Value::Integer(10), Value::Integer(3))
+	]);
+	let mut b = Value::Map(vec![
+	    (Value::Integer(10), Value::Integer(3)),
+	    (Value::Text(String::from("a")), Value::Integer(2)),
+	    (Value::Integer(100), Value::Map(vec![
+		(Value::Integer(10), Value::Null),
+		(Value::Integer(-1), Value::Null)
+	    ])),
+	    (Value::Text(String::from("bb")), Value::Integer(1))
+	]);
+	assert_ne!(a, b);
+
+	a.sort_keys();
+	b.sort_keys();
+	assert_eq!(a, b);
+	assert_eq!(encode_to_vec(&a), [
+	    0xA4,
+	    0x0A, 0x03,
+	    0x18, 0x64, 0xA2, 0x0A, 0xF6, 0x20, 0xF6,
+	    0x61, 0x61, 0x02,
+	    0x62, 0x62, 0x62, 0x01
+	]);
+    }
+
+    #[test]
+    fn test_decode_huge_length() {
+	assert_eq!(Value::decode(&[0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x02]), Err(Error::UnexpectedEnd));
+	assert_eq!(Value::decode(&[0xBB, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x02]), Err(Error::UnexpectedEnd));
+	assert_eq!(capacity_hint(u64::MAX, &[0x01, 0x02], 1), 2);
+	assert_eq!(capacity_hint(u64::MAX, &[0x01, 0x02], 2), 1);
+	assert_eq!(capacity_hint(1, &[0x01, 0x02], 1), 1);
+    }
+
+    #[test]
+    fn test_decode_single() {
+	assert_eq!(decode_single(&[0x01]), Ok(Value::Integer(1)));
+	assert_eq!(decode_single(&[0x01, 0x02]), Err(Error::TrailingData { offset: 1 }));
+	assert_eq!(decode_single(&[0x82, 0x01, 0x02, 0xF6]), Err(Error::TrailingData { offset: 3 }));
+	assert_eq!(decode_single(&[]), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_decode_split_utf8() {
+	assert_eq!(Value::decode(&[0x7F, 0x62, 0xF0, 0x9F, 0x62, 0x98, 0x80, 0xFF]), Ok(Value::Text(String::from("\u{1F600}"))));
+	assert_eq!(Value::decode(&[0x7F, 0x62, 0xF0, 0x9F, 0x61, 0x98, 0xFF]), Err(Error::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_try_from() {
+	let data: &[u8] = &[0xA2, 0x61, 0x61, 0x01, 0x61, 0x62, 0xF5];
+	let value: Value = data.try_into().unwrap();
+
+	assert_eq!(value, Value::Map(vec![
+	    (Value::Text(String::from("a")), Value::Integer(1)),
+	    (Value::Text(String::from("b")), Value::Bool(true))
+	]));
+	assert_eq!(Value::try_from(&[0x82, 0x01][..]), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_decode() {
+	let value = Value::decode(&[
+	    0xA3,
+	    0x61, 0x61, 0x83, 0x01, 0x20, 0xF9, 0x3E, 0x00,
+	    0x42, 0x01, 0x02, 0xC1, 0x1A, 0x51, 0x4B, 0x67, 0xB0,
+	    0xF5, 0x83, 0xF4, 0xF6, 0xF7
+	]);
+
+	assert_eq!(value, Ok(Value::Map(vec![
+	    (Value::Text(String::from("a")), Value::Array(vec![Value::Integer(1), Value::Integer(-1), Value::Float(1.5)])),
+	    (Value::Bytes(vec![0x01, 0x02]), Value::Tag(1, Box::new(Value::Integer(1363896240)))),
+	    (Value::Bool(true), Value::Array(vec![Value::Bool(false), Value::Null, Value::Undefined]))
+	])));
+    }
+
+    #[test]
+    fn test_decode_indefinite() {
+	let value = Value::decode(&[
+	    0xBF,
+	    0x7F, 0x61, 0x61, 0x61, 0x62, 0xFF,
+	    0x9F, 0x5F, 0x41, 0x01, 0xFF, 0xC2, 0xC3, 0xF8, 0x20, 0xFF,
+	    0xFF
+	]);
+
+	assert_eq!(value, Ok(Value::Map(vec![
+	    (Value::Text(String::from("ab")), Value::Array(vec![
+		Value::Bytes(vec![0x01]),
+		Value::Tag(2, Box::new(Value::Tag(3, Box::new(Value::Simple(32)))))
+	    ]))
+	])));
+    }
+
+    #[test]
+    fn test_decode_err() {
+	assert_eq!(Value::decode(&[0x82, 0x01]), Err(Error::UnexpectedEnd));
+	assert_eq!(Value::decode(&[0x9F, 0x01]), Err(Error::UnexpectedEnd));
+	assert_eq!(Value::decode(&[0x61, 0xFF]), Err(Error::InvalidUtf8));
+	assert_eq!(Value::decode(&[0x81, 0xFF]), Err(Error::UnexpectedBreak));
+	assert_eq!(Value::decode(&[]), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+	let data = [
+	    0xA2,
+	    0x61, 0x61, 0xA2, 0x01, 0x82, 0x20, 0xF9, 0x3E, 0x00, 0x02, 0xC1, 0xFA, 0x47, 0xC3, 0x50, 0x00,
+	    0x62, 0x62, 0x63, 0x84, 0x42, 0x01, 0x02, 0xF5, 0xF6, 0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A
+	];
+
+	let value = Value::decode(&data).unwrap();
+	assert_eq!(encode_to_vec(&value), data);
+    }
+
+    #[test]
+    fn test_encode_shortest() {
+	assert_eq!(encode_to_vec(&Value::Array(vec![
+	    Value::Integer(1000),
+	    Value::Integer(-1000),
+	    Value::Float(1.0),
+	    Value::Float(100000.0),
+	    Value::Float(f64::NAN)
+	])), [
+	    0x85,
+	    0x19, 0x03, 0xE8,
+	    0x39, 0x03, 0xE7,
+	    0xF9, 0x3C, 0x00,
+	    0xFA, 0x47, 0xC3, 0x50, 0x00,
+	    0xF9, 0x7E, 0x00
+	]);
+    }
+
+    #[test]
+    fn test_encode_bignum() {
+	assert_eq!(encode_to_vec(&Value::Integer(0x1_0000_0000_0000_0000)), [
+	    0xC2, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+	]);
+	assert_eq!(encode_to_vec(&Value::Integer(-0x1_0000_0000_0000_0001)), [
+	    0xC3, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+	]);
+	assert_eq!(encode_to_vec(&Value::Integer(-0x1_0000_0000_0000_0000)), [
+	    0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+	]);
+    }
+
+}